@@ -0,0 +1,117 @@
+//! Servidor HTTP con `ArcaMock`, para tests de integracion sin red.
+//!
+//! `arca-mock [--addr 127.0.0.1:8080] [--timeout-secs 120] [--fault operacion=falla]...`
+//!
+//! Las fallas (`already_authenticated`, `10016`, `timeout`, `soap_fault`) se consumen en el proximo pedido de la operacion.
+//! Tambien se pueden inyectar con `POST /mock/fault/{operacion}/{falla}`.
+//! Las URLs de WSAA y WSFEv1 son `/ws/services/LoginCms` y `/wsfev1/service.asmx`; ver `ArcaMock::environment`
+
+#![allow(clippy::needless_return)]
+
+use std::{io::{BufRead, BufReader, Read, Write}, net::{TcpListener, TcpStream}, sync::Arc, thread, time::Duration};
+
+use integracion_arca::{mock::{ArcaMock, MockFault}, SoapRequest, SoapResponse};
+
+fn main() {
+	let mut addr = "127.0.0.1:8080".to_owned();
+	let mut demora = Duration::from_secs(120);
+	let mock = Arc::new(ArcaMock::new());
+
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut valor = || args.next().unwrap_or_else(|| salir(&format!("Falta el valor de {arg}")));
+		match arg.as_str() {
+			"--addr" => addr = valor(),
+			"--timeout-secs" => demora = Duration::from_secs(valor().parse().unwrap_or_else(|_| salir("--timeout-secs tiene que ser un numero"))),
+			"--fault" => {
+				let valor = valor();
+				let Some((operacion, falla)) = valor.split_once('=') else { salir("--fault espera operacion=falla") };
+				mock.inject_fault(operacion, falla.parse().unwrap_or_else(|err:String| salir(&err)));
+			},
+			otro => salir(&format!("Argumento desconocido: {otro}")),
+		}
+	}
+
+	let listener = TcpListener::bind(&addr).unwrap_or_else(|err| salir(&format!("No se pudo escuchar en {addr}: {err}")));
+	println!("arca-mock escuchando en http://{addr}");
+	for stream in listener.incoming().flatten() {
+		let mock = mock.clone();
+		thread::spawn(move || {
+			if let Err(err) = atender(&mock, stream, demora) {
+				eprintln!("arca-mock: {err}");
+			}
+		});
+	}
+}
+
+fn atender(mock:&ArcaMock, mut stream:TcpStream, demora:Duration) -> std::io::Result<()> {
+	let mut reader = BufReader::new(stream.try_clone()?);
+	let mut linea = String::new();
+	reader.read_line(&mut linea)?;
+	let mut partes = linea.split_whitespace();
+	let (metodo, path) = (partes.next().unwrap_or_default().to_owned(), partes.next().unwrap_or_default().to_owned());
+
+	let mut headers = Vec::new();
+	loop {
+		let mut header = String::new();
+		if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+			break;
+		}
+		if let Some((nombre, valor)) = header.split_once(':') {
+			headers.push((nombre.trim().to_ascii_lowercase(), valor.trim().to_owned()));
+		}
+	}
+	let header = |nombre:&str| headers.iter().find(|(n, _)| n == nombre).map(|(_, v)| v.clone());
+
+	let largo = header("content-length").and_then(|largo| largo.parse().ok()).unwrap_or(0);
+	let mut body = vec![0; largo];
+	reader.read_exact(&mut body)?;
+
+	if let Some(falla) = path.strip_prefix("/mock/fault/") && metodo == "POST" {
+		let respuesta = match falla.split_once('/').map(|(operacion, falla)| (operacion, falla.parse::<MockFault>())) {
+			Some((operacion, Ok(falla))) => {
+				mock.inject_fault(operacion, falla);
+				SoapResponse { status: 204, headers: Vec::new(), body: String::new() }
+			},
+			Some((_, Err(err))) => SoapResponse { status: 400, headers: Vec::new(), body: err },
+			None => SoapResponse { status: 400, headers: Vec::new(), body: "Se espera /mock/fault/{operacion}/{falla}".to_owned() },
+		};
+		return responder(&mut stream, &respuesta);
+	}
+
+	let content_type = header("content-type").unwrap_or_else(|| "text/xml".to_owned());
+	//En SOAP 1.2 la accion viaja en el content-type
+	let soap_action = header("soapaction")
+		.or_else(|| content_type.split_once("action=").map(|(_, accion)| accion.to_owned()))
+		.map(|accion| accion.trim_matches('"').to_owned());
+	let req = SoapRequest { url: path, soap_action, content_type, body: String::from_utf8_lossy(&body).into_owned(), timeout: None, cuit: None, webservice: None };
+
+	match mock.handle(&req) {
+		Some(respuesta) => return responder(&mut stream, &respuesta),
+		None => {
+			thread::sleep(demora);
+			return Ok(());
+		},
+	}
+}
+
+fn responder(stream:&mut TcpStream, respuesta:&SoapResponse) -> std::io::Result<()> {
+	let razon = match respuesta.status {
+		200 => "OK",
+		204 => "No Content",
+		400 => "Bad Request",
+		_   => "Internal Server Error",
+	};
+	let mut salida = format!("HTTP/1.1 {} {razon}\r\n", respuesta.status);
+	for (nombre, valor) in &respuesta.headers {
+		salida += &format!("{nombre}: {valor}\r\n");
+	}
+	salida += &format!("content-length: {}\r\nconnection: close\r\n\r\n{}", respuesta.body.len(), respuesta.body);
+	stream.write_all(salida.as_bytes())?;
+	return stream.flush();
+}
+
+fn salir(msg:&str) -> ! {
+	eprintln!("arca-mock: {msg}");
+	std::process::exit(2);
+}
@@ -0,0 +1,118 @@
+//! Versiones sincronicas de la API, sobre `ureq`. No necesitan un runtime de tokio.
+//!
+//! Cada funcion tiene la misma firma que su version async, y se usa con un transporte que no necesite runtime,
+//! normalmente `ureq::Agent`. El `Agent` se configura igual que el `Client`: la libreria no activa ninguna implementacion de TLS de `ureq`.
+//! Los transportes que esperan, `RetryTransport` y `RateLimitedTransport`, se arman con `with_sleep(sleep)`.
+
+use std::{future::Future, pin::pin, sync::Arc, task::{Context, Poll, Wake, Waker}, thread::{self, Thread}, time::Duration};
+
+use crate::transport::{SoapRequest, SoapResponse, SoapTransport, TransportError};
+
+#[cfg(feature = "wsaa")]
+pub use crate::wsaa::blocking::get_token;
+
+macro_rules! service_status {
+	($feature:literal, $servicio:ident) => {
+		pub mod $servicio {
+			///Igual que la version async: consulta el metodo dummy para saber si el servicio esta corriendo o no
+			#[cfg(feature = $feature)]
+			pub fn service_status<T: crate::transport::SoapTransport>(transport:&T, environment:&crate::types::enums::ArcaEnvironment, timeout:Option<std::time::Duration>) -> crate::types::FEDummyResult {
+				return super::block_on(crate::$servicio::service_status(transport, environment, timeout));
+			}
+		}
+	};
+}
+
+service_status!("wsfexv1_dummy", wsfexv1);
+service_status!("wsmtxca_dummy", wsmtxca);
+service_status!("wscpe_dummy", wscpe);
+service_status!("wslpg_dummy", wslpg);
+service_status!("wsbfev1_dummy", wsbfev1);
+
+pub mod wsfev1 {
+	///Igual que la version async: consulta el metodo FEDummy para saber si el servicio esta corriendo o no
+	#[cfg(feature = "wsfev1_dummy")]
+	pub fn service_status<T: crate::transport::SoapTransport>(transport:&T, environment:&crate::types::enums::ArcaEnvironment, timeout:Option<std::time::Duration>) -> crate::types::FEDummyResult {
+		return super::block_on(crate::wsfev1::service_status(transport, environment, timeout));
+	}
+
+	#[cfg(feature = "wsfev1_fe_cae_solicitar")]
+	pub use crate::wsfev1::fe_cae_solicitar::blocking::generar_request;
+}
+
+
+///Envia un request, por ejemplo el de `wsfev1::generar_request`
+pub fn send<T: SoapTransport>(transport:&T, req:SoapRequest) -> Result<SoapResponse, TransportError> {
+	return block_on(transport.send(req));
+}
+
+///Espera bloqueando el thread. Reemplaza a `tokio::time::sleep`, que sin runtime entra en panico:
+///`RetryTransport::new(agent, policy).with_sleep(blocking::sleep)`
+pub async fn sleep(duration:Duration) {
+	thread::sleep(duration);
+}
+
+///Ejecuta un future en el thread actual, sin runtime. Solo sirve para futures que no dependan de uno,
+///como los de `TokenStore` para `DashMap` y `FileTokenStore` o los de `tokio::sync`
+pub(crate) fn block_on<F: Future>(fut: F) -> F::Output {
+	struct Despertador(Thread);
+	impl Wake for Despertador {
+		fn wake(self: Arc<Self>) {
+			self.0.unpark();
+		}
+	}
+
+	let waker = Waker::from(Arc::new(Despertador(thread::current())));
+	let mut cx = Context::from_waker(&waker);
+	let mut fut = pin!(fut);
+	loop {
+		match fut.as_mut().poll(&mut cx) {
+			Poll::Ready(res) => return res,
+			Poll::Pending => thread::park(),
+		}
+	}
+}
+
+
+#[cfg(all(test, feature = "wsaa"))]
+mod tests {
+	use chrono::{Duration, Utc};
+	use dashmap::DashMap;
+
+	use std::sync::atomic::{AtomicU32, Ordering};
+
+	use crate::{transport::{MemoryTransport, TransportErrorKind}, types::enums::{ArcaEnvironment, Webservice}, wsaa::get_token::{una_renovacion, ServiceId, TokenArca}, RetryPolicy, RetryTransport};
+
+	use super::*;
+
+	//Sin #[tokio::test]: la renovacion compartida tiene que andar sin runtime
+	#[test]
+	fn renueva_sin_runtime() {
+		let store = DashMap::<ServiceId, TokenArca>::new();
+		let key = ServiceId::new(3, Webservice::Wsfev1, ArcaEnvironment::Homologation);
+		let token = block_on(una_renovacion(&store, key.clone(), Duration::minutes(15), async |_| {
+			Ok(TokenArca::de_prueba("tok", Utc::now() + Duration::hours(12)))
+		})).unwrap();
+		assert_eq!(token.token(), "tok");
+		assert!(store.contains_key(&key));
+	}
+
+	#[test]
+	fn reintenta_sin_runtime() {
+		let intentos = AtomicU32::new(0);
+		let transport = RetryTransport::new(MemoryTransport::new(move |_| match intentos.fetch_add(1, Ordering::SeqCst) {
+			0 => Err(TransportError::new(TransportErrorKind::Connect, "connection refused")),
+			_ => Ok(SoapResponse::ok(String::new())),
+		}), RetryPolicy { base_delay: std::time::Duration::from_millis(1), ..RetryPolicy::default() }).with_sleep(sleep);
+		let pedido = SoapRequest {
+			url						: "http://127.0.0.1/wsfev1/service.asmx".to_owned(),
+			soap_action		: None,
+			content_type	: "application/soap+xml".to_owned(),
+			body					: "<soap:Body><ar:FEDummy/></soap:Body>".to_owned(),
+			timeout				: None,
+			cuit					: None,
+			webservice		: None,
+		};
+		assert!(send(&transport, pedido).is_ok());
+	}
+}
@@ -0,0 +1,260 @@
+use std::{collections::HashMap, sync::Mutex, time::{Duration, Instant}};
+
+use chrono::Utc;
+
+use crate::{transport::{SoapRequest, SoapResponse, SoapTransport}, types::{enums::{ArcaEnvironment, Webservice}, errors::{ErrType, ErrorClass}}};
+
+///Cuando abrir un circuito y cada cuanto probar si el servicio volvio
+#[derive(Debug, Clone)]
+pub struct CircuitConfig {
+	///Fallas de transporte seguidas que abren el circuito
+	pub failure_threshold	: u32,
+	///Cuanto se espera, con el circuito abierto, entre una prueba y la siguiente
+	pub probe_interval		: Duration,
+	///Timeout de la consulta al dummy
+	pub probe_timeout			: Duration,
+}
+
+impl Default for CircuitConfig {
+	fn default() -> Self {
+		Self {
+			failure_threshold	: 5,
+			probe_interval		: Duration::from_secs(30),
+			probe_timeout			: Duration::from_secs(10),
+		}
+	}
+}
+
+///Estado del circuito de un servicio en un ambiente
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+	///Los pedidos pasan normalmente
+	Closed,
+	///El servicio no responde. Los pedidos fallan al instante con `ErrType::CircuitOpen` hasta que una prueba salga bien
+	Open { next_probe: Instant },
+	///Hay una prueba en curso. Si no termina antes de `until`, otra llamada puede volver a probar
+	HalfOpen { until: Instant },
+}
+
+struct Circuito {
+	estado: CircuitState,
+	fallas: u32,
+}
+
+///Corta los pedidos a un servicio de ARCA que esta caido, para no acumular workers esperando timeouts de 60 segundos.
+///
+///Lleva un circuito por servicio y ambiente. Se abre despues de `failure_threshold` fallas de transporte seguidas, y mientras
+///esta abierto prueba cada `probe_interval` con el `service_status` del servicio. Se cierra cuando el dummy responde
+///`app_server`, `db_server` y `auth_server` en OK. Si la feature del dummy no esta activa, la prueba es el pedido siguiente.
+///
+///Con `state` se puede desviar la facturacion a un circuito de contingencia (por ejemplo CAEA) mientras el servicio no vuelve.
+///En ese caso conviene llamar a `probe_due` periodicamente, porque nadie mas va a probar el servicio
+pub struct CircuitBreaker {
+	config		: CircuitConfig,
+	circuitos	: Mutex<HashMap<(Webservice, ArcaEnvironment), Circuito>>,
+}
+
+impl CircuitBreaker {
+	pub fn new(config:CircuitConfig) -> Self {
+		CircuitBreaker { config, circuitos: Mutex::new(HashMap::new()) }
+	}
+
+	pub fn state(&self, webservice:&Webservice, environment:&ArcaEnvironment) -> CircuitState {
+		return self.circuitos().get(&(webservice.clone(), environment.clone())).map_or(CircuitState::Closed, |c| c.estado);
+	}
+
+	///Envia el pedido si el circuito lo permite y registra el resultado
+	pub async fn send<T: SoapTransport>(&self, transport:&T, webservice:&Webservice, environment:&ArcaEnvironment, req:SoapRequest) -> Result<SoapResponse, ErrType> {
+		self.check(transport, webservice, environment, req.timeout).await?;
+
+		match transport.send(req).await {
+			Ok(res) if matches!(res.status, 502..=504) && !res.body.contains("Fault") => {
+				self.record_failure(webservice, environment);
+				return Ok(res);
+			},
+			Ok(res) => {
+				self.record_success(webservice, environment);
+				return Ok(res);
+			},
+			Err(err) => {
+				let err = ErrType::from(err);
+				if matches!(err.class(), ErrorClass::Transport | ErrorClass::OutcomeUnknown) {
+					self.record_failure(webservice, environment);
+				}
+				return Err(err);
+			},
+		}
+	}
+
+	///Para llamar antes de un pedido que no pasa por `send`. Falla con `ErrType::CircuitOpen` si el circuito esta abierto,
+	///y si ya toca, prueba el servicio
+	pub async fn check<T: SoapTransport>(&self, transport:&T, webservice:&Webservice, environment:&ArcaEnvironment, timeout:Option<Duration>) -> Result<(), ErrType> {
+		let ahora = Instant::now();
+		let hasta = ahora + timeout.unwrap_or(Duration::from_secs(60)).max(self.config.probe_timeout);
+		{
+			let mut circuitos = self.circuitos();
+			let Some(circuito) = circuitos.get_mut(&(webservice.clone(), environment.clone())) else { return Ok(()) };
+			match circuito.estado {
+				CircuitState::Closed => return Ok(()),
+				CircuitState::Open { next_probe: limite } | CircuitState::HalfOpen { until: limite } if limite > ahora => {
+					return Err(ErrType::CircuitOpen { webservice: webservice.clone(), retry_after: Utc::now() + (limite - ahora) });
+				},
+				_ => circuito.estado = CircuitState::HalfOpen { until: hasta },
+			}
+		}
+
+		match self.probar(transport, webservice, environment).await {
+			//Sin dummy, este pedido es la prueba
+			None | Some(CircuitState::Closed) => return Ok(()),
+			Some(_) => return Err(ErrType::CircuitOpen { webservice: webservice.clone(), retry_after: Utc::now() + self.config.probe_interval }),
+		}
+	}
+
+	pub fn record_success(&self, webservice:&Webservice, environment:&ArcaEnvironment) {
+		self.circuitos().remove(&(webservice.clone(), environment.clone()));
+	}
+
+	pub fn record_failure(&self, webservice:&Webservice, environment:&ArcaEnvironment) {
+		let mut circuitos = self.circuitos();
+		let circuito = circuitos.entry((webservice.clone(), environment.clone())).or_insert(Circuito { estado: CircuitState::Closed, fallas: 0 });
+		circuito.fallas += 1;
+		if circuito.fallas >= self.config.failure_threshold || matches!(circuito.estado, CircuitState::HalfOpen { .. }) {
+			if circuito.estado == CircuitState::Closed {
+				tracing::warn!(%webservice, environment = environment.name(), fallas = circuito.fallas, "Se abre el circuito");
+			}
+			circuito.estado = CircuitState::Open { next_probe: Instant::now() + self.config.probe_interval };
+		}
+	}
+
+	///Prueba los circuitos abiertos a los que ya les toca, con el dummy de cada servicio
+	pub async fn probe_due<T: SoapTransport>(&self, transport:&T) {
+		let ahora = Instant::now();
+		let vencidos: Vec<_> = self.circuitos().iter_mut()
+			.filter(|(_, c)| matches!(c.estado, CircuitState::Open { next_probe } if next_probe <= ahora))
+			.map(|(key, c)| {
+				c.estado = CircuitState::HalfOpen { until: ahora + self.config.probe_timeout };
+				key.clone()
+			})
+			.collect();
+
+		for (webservice, environment) in vencidos {
+			if self.probar(transport, &webservice, &environment).await.is_none() {
+				//Sin dummy no hay como probar: queda esperando el proximo pedido
+				self.circuitos().entry((webservice, environment)).and_modify(|c| c.estado = CircuitState::Open { next_probe: ahora });
+			}
+		}
+	}
+
+	///Consulta el dummy y deja el circuito cerrado o abierto. `None` si el servicio no tiene dummy con las features activas
+	async fn probar<T: SoapTransport>(&self, transport:&T, webservice:&Webservice, environment:&ArcaEnvironment) -> Option<CircuitState> {
+		let ok = dummy_ok(transport, webservice, environment, self.config.probe_timeout).await?;
+		if ok {
+			tracing::info!(%webservice, environment = environment.name(), "Se cierra el circuito");
+			self.record_success(webservice, environment);
+			return Some(CircuitState::Closed);
+		}
+
+		let estado = CircuitState::Open { next_probe: Instant::now() + self.config.probe_interval };
+		self.circuitos().entry((webservice.clone(), environment.clone())).and_modify(|c| c.estado = estado);
+		return Some(estado);
+	}
+
+	fn circuitos(&self) -> std::sync::MutexGuard<'_, HashMap<(Webservice, ArcaEnvironment), Circuito>> {
+		self.circuitos.lock().unwrap_or_else(|err| err.into_inner())
+	}
+}
+
+impl Default for CircuitBreaker {
+	fn default() -> Self {
+		Self::new(CircuitConfig::default())
+	}
+}
+
+
+#[allow(unused_variables)]
+async fn dummy_ok<T: SoapTransport>(transport:&T, webservice:&Webservice, environment:&ArcaEnvironment, timeout:Duration) -> Option<bool> {
+	#[cfg(feature = "dummy_type")]
+	let ok = |res:crate::types::FEDummyResult| res.status == 200 && res.app_server && res.db_server && res.auth_server;
+
+	return match webservice {
+		#[cfg(feature = "wsfev1_dummy")]
+		Webservice::Wsfev1  => Some(ok(crate::wsfev1::service_status(transport, environment, Some(timeout)).await)),
+		#[cfg(feature = "wsfexv1_dummy")]
+		Webservice::Wsfexv1 => Some(ok(crate::wsfexv1::service_status(transport, environment, Some(timeout)).await)),
+		#[cfg(feature = "wsmtxca_dummy")]
+		Webservice::Wsmtxca => Some(ok(crate::wsmtxca::service_status(transport, environment, Some(timeout)).await)),
+		#[cfg(feature = "wscpe_dummy")]
+		Webservice::Wscpe   => Some(ok(crate::wscpe::service_status(transport, environment, Some(timeout)).await)),
+		#[cfg(feature = "wslpg_dummy")]
+		Webservice::Wslpg   => Some(ok(crate::wslpg::service_status(transport, environment, Some(timeout)).await)),
+		#[cfg(feature = "wsbfev1_dummy")]
+		Webservice::Wsbfev1 => Some(ok(crate::wsbfev1::service_status(transport, environment, Some(timeout)).await)),
+		_ => None,
+	};
+}
+
+
+#[cfg(test)]
+mod tests {
+	#[cfg(feature = "mock")]
+	use crate::mock::{ArcaMock, MockFault};
+	use crate::transport::TransportErrorKind;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn abre_con_timeouts_de_reqwest() {
+		//Acepta conexiones y nunca responde
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let breaker = CircuitBreaker::new(CircuitConfig { failure_threshold: 2, ..CircuitConfig::default() });
+		let (ws, environment) = (Webservice::Wsfev1, ArcaEnvironment::Homologation);
+		let pedido = SoapRequest {
+			url						: format!("http://{}/wsfev1/service.asmx", listener.local_addr().unwrap()),
+			soap_action		: None,
+			content_type	: "application/soap+xml".to_owned(),
+			body					: "<soap:Body><ar:FECAESolicitar/></soap:Body>".to_owned(),
+			timeout				: Some(Duration::from_millis(200)),
+			cuit					: None,
+			webservice		: None,
+		};
+
+		for _ in 0..2 {
+			let err = breaker.send(&reqwest::Client::new(), &ws, &environment, pedido.clone()).await.unwrap_err();
+			assert!(matches!(err, ErrType::Transport(err) if err.kind == TransportErrorKind::Timeout));
+		}
+		assert!(matches!(breaker.state(&ws, &environment), CircuitState::Open { .. }));
+		assert!(matches!(breaker.send(&reqwest::Client::new(), &ws, &environment, pedido).await, Err(ErrType::CircuitOpen { .. })));
+	}
+
+	#[cfg(feature = "mock")]
+	#[tokio::test]
+	async fn abre_y_cierra_con_el_dummy() {
+		let breaker = CircuitBreaker::new(CircuitConfig { failure_threshold: 2, probe_interval: Duration::ZERO, ..CircuitConfig::default() });
+		let mock = ArcaMock::new();
+		let environment = ArcaMock::environment("http://127.0.0.1:8080");
+		let ws = Webservice::Wsfev1;
+		let pedido = SoapRequest {
+			url						: "http://127.0.0.1:8080/wsfev1/service.asmx".to_owned(),
+			soap_action		: None,
+			content_type	: "application/soap+xml".to_owned(),
+			body					: "<soap:Body><ar:FECompUltimoAutorizado/></soap:Body>".to_owned(),
+			timeout				: None,
+			cuit					: None,
+			webservice		: None,
+		};
+
+		for _ in 0..2 {
+			mock.inject_fault("FECompUltimoAutorizado", MockFault::Timeout);
+			let err = breaker.send(&mock, &ws, &environment, pedido.clone()).await.unwrap_err();
+			assert!(matches!(err, ErrType::Transport(err) if err.kind == TransportErrorKind::Timeout));
+		}
+		assert!(matches!(breaker.state(&ws, &environment), CircuitState::Open { .. }));
+
+		mock.inject_fault("FEDummy", MockFault::SoapFault { code: "soap:Server".to_owned(), message: "caido".to_owned() });
+		breaker.probe_due(&mock).await;
+		assert!(matches!(breaker.state(&ws, &environment), CircuitState::Open { .. }));
+
+		assert!(breaker.send(&mock, &ws, &environment, pedido).await.is_ok());
+		assert_eq!(breaker.state(&ws, &environment), CircuitState::Closed);
+	}
+}
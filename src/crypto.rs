@@ -1 +1,8 @@
-pub mod sign_cms;
\ No newline at end of file
+pub mod load;
+pub mod sign_cms;
+pub mod signer;
+
+#[cfg(feature = "pkcs11")]
+mod cms_der;
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
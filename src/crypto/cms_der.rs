@@ -0,0 +1,139 @@
+//! Arma a mano un CMS SignedData en DER, para firmantes que no exponen la llave privada a OpenSSL.
+//! Produce lo mismo que `CmsContentInfo::sign` con SHA-256, RSA y el contenido incluido.
+
+use chrono::Utc;
+use openssl::{sha::sha256, x509::X509Ref};
+
+use crate::types::errors::ErrType;
+
+const OID_SIGNED_DATA		: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02];
+const OID_DATA					: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x01];
+const OID_SHA256				: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_RSA						: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01];
+const OID_CONTENT_TYPE	: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x03];
+const OID_MESSAGE_DIGEST: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x04];
+const OID_SIGNING_TIME	: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x05];
+
+const SEQUENCE	: u8 = 0x30;
+const SET				: u8 = 0x31;
+const INTEGER		: u8 = 0x02;
+const OCTETS		: u8 = 0x04;
+const NULL			: u8 = 0x05;
+const OID				: u8 = 0x06;
+const UTC_TIME	: u8 = 0x17;
+const CONTEXT_0	: u8 = 0xA0;
+
+
+///Devuelve el DER del ContentInfo. `firmar` recibe los atributos firmados y debe devolver la firma RSA PKCS#1 v1.5 con SHA-256
+pub fn signed_data<F>(cert:&X509Ref, contenido:&[u8], firmar:F) -> Result<Vec<u8>, ErrType>
+where
+	F: FnOnce(&[u8]) -> Result<Vec<u8>, ErrType>,
+{
+	let cert_der = cert.to_der().map_err(|err| ErrType::Signer(format!("No se pudo leer el certificado: {err}")))?;
+	let issuer   = cert.issuer_name().to_der().map_err(|err| ErrType::Signer(format!("No se pudo leer el emisor del certificado: {err}")))?;
+	let serial   = cert.serial_number().to_bn().map_err(|err| ErrType::Signer(format!("No se pudo leer el numero de serie del certificado: {err}")))?.to_vec();
+
+	let sha256_alg = tlv(SEQUENCE, &[tlv(OID, OID_SHA256), tlv(NULL, &[])].concat());
+	let rsa_alg    = tlv(SEQUENCE, &[tlv(OID, OID_RSA), tlv(NULL, &[])].concat());
+
+	let signing_time = Utc::now().format("%y%m%d%H%M%SZ").to_string();
+	let mut atributos = [
+		atributo(OID_CONTENT_TYPE, &tlv(OID, OID_DATA)),
+		atributo(OID_SIGNING_TIME, &tlv(UTC_TIME, signing_time.as_bytes())),
+		atributo(OID_MESSAGE_DIGEST, &tlv(OCTETS, &sha256(contenido))),
+	];
+	//DER exige que los elementos de un SET OF esten ordenados
+	atributos.sort();
+	let atributos = atributos.concat();
+
+	//La firma se calcula sobre los atributos codificados como SET, aunque dentro del SignerInfo lleven el tag [0]
+	let firma = firmar(&tlv(SET, &atributos))?;
+
+	let signer_info = tlv(SEQUENCE, &[
+		tlv(INTEGER, &[1]),
+		tlv(SEQUENCE, &[issuer, entero(&serial)].concat()),
+		sha256_alg.clone(),
+		tlv(CONTEXT_0, &atributos),
+		rsa_alg,
+		tlv(OCTETS, &firma),
+	].concat());
+
+	let signed_data = tlv(SEQUENCE, &[
+		tlv(INTEGER, &[1]),
+		tlv(SET, &sha256_alg),
+		tlv(SEQUENCE, &[tlv(OID, OID_DATA), tlv(CONTEXT_0, &tlv(OCTETS, contenido))].concat()),
+		tlv(CONTEXT_0, &cert_der),
+		tlv(SET, &signer_info),
+	].concat());
+
+	Ok(tlv(SEQUENCE, &[tlv(OID, OID_SIGNED_DATA), tlv(CONTEXT_0, &signed_data)].concat()))
+}
+
+fn atributo(oid:&[u8], valor:&[u8]) -> Vec<u8> {
+	tlv(SEQUENCE, &[tlv(OID, oid), tlv(SET, valor)].concat())
+}
+
+///INTEGER positivo: si el primer bit esta prendido hace falta un cero adelante
+fn entero(bytes:&[u8]) -> Vec<u8> {
+	match bytes.first() {
+		None => tlv(INTEGER, &[0]),
+		Some(b) if b & 0x80 != 0 => tlv(INTEGER, &[&[0], bytes].concat()),
+		Some(_) => tlv(INTEGER, bytes),
+	}
+}
+
+fn tlv(tag:u8, contenido:&[u8]) -> Vec<u8> {
+	let mut out = vec![tag];
+	let len = contenido.len();
+	if len < 0x80 {
+		out.push(len as u8);
+	} else {
+		let bytes: Vec<u8> = len.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+		out.push(0x80 | bytes.len() as u8);
+		out.extend(bytes);
+	}
+	out.extend_from_slice(contenido);
+	out
+}
+
+
+#[cfg(test)]
+mod tests {
+	use openssl::{asn1::Asn1Time, bn::BigNum, cms::{CMSOptions, CmsContentInfo}, hash::MessageDigest, pkey::PKey, rsa::Rsa, sign::Signer, stack::Stack, x509::{store::X509StoreBuilder, X509NameBuilder, X509}};
+
+	use super::*;
+
+	#[test]
+	fn openssl_verifica_la_firma() {
+		let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+		let mut name = X509NameBuilder::new().unwrap();
+		name.append_entry_by_text("CN", "prueba").unwrap();
+		let name = name.build();
+		let mut cert = X509::builder().unwrap();
+		cert.set_version(2).unwrap();
+		cert.set_serial_number(&BigNum::from_u32(0x80_00_01).unwrap().to_asn1_integer().unwrap()).unwrap();
+		cert.set_subject_name(&name).unwrap();
+		cert.set_issuer_name(&name).unwrap();
+		cert.set_pubkey(&key).unwrap();
+		cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+		cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+		cert.sign(&key, MessageDigest::sha256()).unwrap();
+		let cert = cert.build();
+
+		let tra = b"<loginTicketRequest version=\"1.0\"></loginTicketRequest>";
+		let der = signed_data(&cert, tra, |attrs| {
+			let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+			signer.update(attrs).unwrap();
+			Ok(signer.sign_to_vec().unwrap())
+		}).unwrap();
+
+		let mut cms = CmsContentInfo::from_der(&der).unwrap();
+		let mut certs = Stack::new().unwrap();
+		certs.push(cert.clone()).unwrap();
+		let mut store = X509StoreBuilder::new().unwrap();
+		store.add_cert(cert).unwrap();
+		let mut out = Vec::new();
+		cms.verify(Some(&certs), Some(&store.build()), None, Some(&mut out), CMSOptions::empty()).unwrap();
+		assert_eq!(out, tra);
+	}
+}
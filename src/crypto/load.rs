@@ -0,0 +1,131 @@
+//! Lectura de certificados y llaves en los formatos que suelen exportar los clientes.
+//! El formato se detecta solo: PEM si el contenido empieza con `-----BEGIN`, DER en otro caso.
+
+use openssl::{error::ErrorStack, pkcs12::Pkcs12, pkey::{PKey, Private}, x509::X509};
+
+use crate::types::errors::CryptoError;
+
+
+///Certificado X509 en PEM o DER
+pub fn parse_cert(contents:&[u8]) -> Result<X509, CryptoError> {
+	let res = if es_pem(contents) {
+		X509::from_pem(contents)
+	} else {
+		X509::from_der(contents)
+	};
+	res.map_err(|err| CryptoError::InvalidCert(err.to_string()))
+}
+
+///Llave privada en PEM (PKCS#1 o PKCS#8) o en DER (PKCS#8 o PKCS#1), encriptada o no.
+///`passphrase` solo se usa si la llave esta encriptada
+pub fn parse_key(contents:&[u8], passphrase:Option<&str>) -> Result<PKey<Private>, CryptoError> {
+	let encriptada = esta_encriptada(contents);
+	if encriptada && passphrase.is_none() {
+		return Err(CryptoError::PassphraseRequired);
+	}
+	//Siempre se pasa una passphrase, aunque sea vacia, para que OpenSSL nunca la pida por consola
+	let pass = passphrase.unwrap_or_default().as_bytes();
+
+	let res = if es_pem(contents) {
+		PKey::private_key_from_pem_passphrase(contents, pass)
+	} else if encriptada {
+		PKey::private_key_from_pkcs8_passphrase(contents, pass)
+	} else {
+		PKey::private_key_from_pkcs8(contents).or_else(|_| PKey::private_key_from_der(contents))
+	};
+
+	res.map_err(|err| if encriptada {
+		CryptoError::WrongPassphrase
+	} else {
+		CryptoError::InvalidKey(err.to_string())
+	})
+}
+
+///Archivo `.p12` o `.pfx` con el certificado y la llave privada
+pub fn parse_pkcs12(der:&[u8], password:&str) -> Result<(X509, PKey<Private>), CryptoError> {
+	let parsed = Pkcs12::from_der(der)
+		.and_then(|p12| p12.parse2(password))
+		.map_err(|err| CryptoError::InvalidPkcs12(err.to_string()))?;
+
+	match (parsed.cert, parsed.pkey) {
+		(Some(cert), Some(key)) => Ok((cert, key)),
+		(None, _) => Err(CryptoError::InvalidPkcs12("El archivo no contiene el certificado".to_owned())),
+		(_, None) => Err(CryptoError::InvalidPkcs12("El archivo no contiene la llave privada".to_owned())),
+	}
+}
+
+///Convierte un PKCS#12 en el par de PEM que usa `CertKeyPair`
+pub fn pkcs12_to_pem(der:&[u8], password:&str) -> Result<(Vec<u8>, Vec<u8>), CryptoError> {
+	let (cert, key) = parse_pkcs12(der, password)?;
+	let pem = |res:Result<Vec<u8>, ErrorStack>| res.map_err(|err| CryptoError::InvalidPkcs12(err.to_string()));
+	Ok((pem(cert.to_pem())?, pem(key.private_key_to_pem_pkcs8())?))
+}
+
+fn es_pem(contents:&[u8]) -> bool {
+	contents.trim_ascii_start().starts_with(b"-----BEGIN")
+}
+
+///PEM con `ENCRYPTED PRIVATE KEY` o `Proc-Type: 4,ENCRYPTED`, o DER PKCS#8 encriptado con PBES2 o PBE de PKCS#12
+fn esta_encriptada(contents:&[u8]) -> bool {
+	const OID_PBES2			: &[u8] = &[0x06, 0x09, 0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x05, 0x0D];
+	const OID_PKCS12_PBE: &[u8] = &[0x06, 0x0A, 0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x0C, 0x01];
+
+	if es_pem(contents) {
+		return contents.windows(b"ENCRYPTED".len()).any(|w| w == b"ENCRYPTED");
+	}
+	//El algoritmo de encriptacion esta al principio del EncryptedPrivateKeyInfo
+	let inicio = &contents[..contents.len().min(32)];
+	[OID_PBES2, OID_PKCS12_PBE].iter().any(|oid| inicio.windows(oid.len()).any(|w| w == *oid))
+}
+
+
+#[cfg(test)]
+mod tests {
+	use openssl::{asn1::Asn1Time, hash::MessageDigest, pkcs12::Pkcs12, rsa::Rsa, symm::Cipher, x509::X509NameBuilder};
+
+	use super::*;
+
+	fn par() -> (X509, PKey<Private>) {
+		let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+		let mut name = X509NameBuilder::new().unwrap();
+		name.append_entry_by_text("CN", "prueba").unwrap();
+		let name = name.build();
+		let mut cert = X509::builder().unwrap();
+		cert.set_subject_name(&name).unwrap();
+		cert.set_issuer_name(&name).unwrap();
+		cert.set_pubkey(&key).unwrap();
+		cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+		cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+		cert.sign(&key, MessageDigest::sha256()).unwrap();
+		(cert.build(), key)
+	}
+
+	#[test]
+	fn detecta_formatos() {
+		let (cert, key) = par();
+		assert!(parse_cert(&cert.to_pem().unwrap()).is_ok());
+		assert!(parse_cert(&cert.to_der().unwrap()).is_ok());
+		assert!(parse_key(&key.private_key_to_pem_pkcs8().unwrap(), None).is_ok());
+		assert!(parse_key(&key.private_key_to_pkcs8().unwrap(), None).is_ok());
+		assert!(parse_key(&key.private_key_to_der().unwrap(), None).is_ok());
+
+		let p12 = Pkcs12::builder().name("arca").pkey(&key).cert(&cert).build2("clave").unwrap().to_der().unwrap();
+		assert!(pkcs12_to_pem(&p12, "clave").is_ok());
+		assert!(matches!(pkcs12_to_pem(&p12, "otra"), Err(CryptoError::InvalidPkcs12(_))));
+	}
+
+	#[test]
+	fn llaves_encriptadas() {
+		let (_, key) = par();
+		let pem = key.private_key_to_pem_pkcs8_passphrase(Cipher::aes_256_cbc(), b"secreto").unwrap();
+		let der = key.private_key_to_pkcs8_passphrase(Cipher::aes_256_cbc(), b"secreto").unwrap();
+
+		for contents in [pem, der] {
+			assert!(parse_key(&contents, Some("secreto")).is_ok());
+			assert!(matches!(parse_key(&contents, Some("otro")), Err(CryptoError::WrongPassphrase)));
+			assert!(matches!(parse_key(&contents, None), Err(CryptoError::PassphraseRequired)));
+		}
+		//Una llave sin encriptar ignora la passphrase
+		assert!(parse_key(&key.private_key_to_pem_pkcs8().unwrap(), Some("secreto")).is_ok());
+	}
+}
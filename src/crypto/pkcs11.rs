@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use cryptoki::{context::{CInitializeArgs, Pkcs11}, error::{Error, RvError}, mechanism::Mechanism, object::{Attribute, ObjectClass}, session::UserType, slot::Slot, types::AuthPin};
+use openssl::{base64::encode_block, x509::X509};
+
+use crate::{crypto::{cms_der::signed_data, load::parse_cert, signer::TraSigner}, types::errors::ErrType};
+
+
+///Firma el `loginTicketRequest` con una llave RSA que vive en un token PKCS#11 (HSM, SoftHSM, smartcard).
+///La llave privada nunca sale del token; solo se le pide la firma de los atributos del CMS.
+///
+///Para probarlo localmente con SoftHSM:
+///```text
+///softhsm2-util --init-token --free --label arca --pin 1234 --so-pin 1234
+///pkcs11-tool --module /usr/lib/softhsm/libsofthsm2.so --token-label arca --login --pin 1234 \
+///    --write-object llave.key --type privkey --label wsaa
+///```
+pub struct Pkcs11Signer {
+	ctx				: Pkcs11,
+	slot			: Slot,
+	pin				: AuthPin,
+	key_label	: String,
+	cert			: X509,
+	cuit			: i64,
+}
+
+impl Pkcs11Signer {
+	///Carga e inicializa la libreria PKCS#11 del fabricante. El contexto se puede clonar y compartir entre varios `Pkcs11Signer`
+	pub fn open_module(module:impl AsRef<Path>) -> Result<Pkcs11, ErrType> {
+		let ctx = Pkcs11::new(module).map_err(pkcs11_err)?;
+		ctx.initialize(CInitializeArgs::OsThreads).map_err(pkcs11_err)?;
+		Ok(ctx)
+	}
+
+	///`token_label` identifica el token dentro del modulo y `key_label` la llave privada dentro del token.
+	///`cert_contents` es el certificado emitido por ARCA para esa llave, en PEM o DER. Viaja dentro del CMS
+	pub fn new(ctx:Pkcs11, token_label:&str, pin:&str, key_label:&str, cert_contents:&[u8], cuit:i64) -> Result<Self, ErrType> {
+		let cert = parse_cert(cert_contents)?;
+
+		let mut slot = None;
+		for candidato in ctx.get_slots_with_token().map_err(pkcs11_err)? {
+			if ctx.get_token_info(candidato).map_err(pkcs11_err)?.label().trim() == token_label {
+				slot = Some(candidato);
+				break;
+			}
+		}
+		let slot = slot.ok_or_else(|| ErrType::Signer(format!("No se encontro el token PKCS#11 '{token_label}'")))?;
+
+		Ok(Self { ctx, slot, pin: AuthPin::from(pin.to_owned()), key_label: key_label.to_owned(), cert, cuit })
+	}
+
+	fn firmar(&self, datos:&[u8]) -> Result<Vec<u8>, ErrType> {
+		let session = self.ctx.open_ro_session(self.slot).map_err(pkcs11_err)?;
+		match session.login(UserType::User, Some(&self.pin)) {
+			//El login es por aplicacion: otra sesion abierta ya pudo haberlo hecho
+			Err(Error::Pkcs11(RvError::UserAlreadyLoggedIn, _)) | Ok(()) => {},
+			Err(err) => return Err(pkcs11_err(err)),
+		}
+
+		let key = session.find_objects(&[
+			Attribute::Class(ObjectClass::PRIVATE_KEY),
+			Attribute::Label(self.key_label.as_bytes().to_vec()),
+		]).map_err(pkcs11_err)?
+			.into_iter().next()
+			.ok_or_else(|| ErrType::Signer(format!("No se encontro la llave '{}' en el token", self.key_label)))?;
+
+		session.sign(&Mechanism::Sha256RsaPkcs, key, datos).map_err(pkcs11_err)
+	}
+}
+
+impl TraSigner for Pkcs11Signer {
+	fn cuit(&self) -> i64 {
+		self.cuit
+	}
+
+	fn sign_tra(&self, tra:&str) -> Result<String, ErrType> {
+		let der = signed_data(&self.cert, tra.as_bytes(), |attrs| self.firmar(attrs))?;
+		Ok(encode_block(&der))
+	}
+}
+
+fn pkcs11_err(err:Error) -> ErrType {
+	ErrType::Signer(format!("PKCS#11: {err}"))
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	///Necesita un token de SoftHSM preparado como indica la documentacion de `Pkcs11Signer`, y las variables
+	///`SOFTHSM2_MODULE`, `PKCS11_TOKEN`, `PKCS11_PIN`, `PKCS11_KEY` y `PKCS11_CERT` (ruta al PEM)
+	#[test]
+	#[ignore]
+	fn firma_con_softhsm() {
+		let var = |name:&str| std::env::var(name).unwrap_or_else(|_| panic!("Falta la variable {name}"));
+		let ctx = Pkcs11Signer::open_module(var("SOFTHSM2_MODULE")).unwrap();
+		let cert = std::fs::read(var("PKCS11_CERT")).unwrap();
+		let signer = Pkcs11Signer::new(ctx, &var("PKCS11_TOKEN"), &var("PKCS11_PIN"), &var("PKCS11_KEY"), &cert, 20111111112).unwrap();
+
+		let cms = signer.sign_tra("<loginTicketRequest/>").unwrap();
+		assert!(!cms.is_empty());
+	}
+}
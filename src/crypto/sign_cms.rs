@@ -1,35 +1,30 @@
 use openssl::cms::CMSOptions;
-use openssl::pkey::PKey;
-use openssl::x509::X509;
+
+use crate::{crypto::load::{parse_cert, parse_key}, types::errors::CryptoError};
 
 /**
- * Recibe data y la encripta y firma con el cert y key recibidos
+ * Recibe data y la encripta y firma con el cert y key recibidos.
+ * El cert y la key pueden venir en PEM o DER. `key_passphrase` solo hace falta si la key esta encriptada
  */
 pub fn sign_cms(
-	cert_contents:&Vec<u8>,
-	key_contents:&Vec<u8>,
+	cert_contents:&[u8],
+	key_contents:&[u8],
+	key_passphrase:Option<&str>,
 	data:&str
-) -> String {
+) -> Result<String, CryptoError> {
 	const LEN_BEGIN:usize = "-----BEGIN CMS-----".len();
 	const LEN_END:usize   = "-----END CMS-----".len()+1;
 
-	/*let cert_contents = fs::read_to_string(cert_path).unwrap();
-	let key_contents = fs::read_to_string(key_path).unwrap();*/
-
-	let cert = X509::from_pem(cert_contents)
-		.inspect_err(|err| {tracing::error!({
-				certif = ?cert_contents,
-				err    = ?err,
-				"No se pudo leer el certificado como X509", 
-		})})
-		.expect("No se pudo leer el certificado como X509");
-	let key  = PKey::private_key_from_pem(key_contents)
-		.inspect_err(|err| {tracing::error!({
-				key    = ?key_contents,
-				err    = ?err,
-				"No se pudo leer la key como PKey", 
-		})})
-		.expect("No se pudo leer la key como PKey");
+	let cert = parse_cert(cert_contents)
+		.inspect_err(|err| tracing::error!(err = ?err, "No se pudo leer el certificado como X509"))?;
+	//Nunca logear el contenido de la llave
+	let key  = parse_key(key_contents, key_passphrase)
+		.inspect_err(|err| tracing::error!(err = ?err, "No se pudo leer la key como PKey"))?;
+
+	let cert_pub = cert.public_key().map_err(|err| CryptoError::InvalidCert(err.to_string()))?;
+	if !key.public_eq(&cert_pub) {
+		return Err(CryptoError::KeyMismatch);
+	}
 
 	let flags = CMSOptions::empty();
 	let mut pem = openssl::cms::CmsContentInfo::sign(
@@ -38,11 +33,29 @@ pub fn sign_cms(
 		None,
 		Some(data.as_bytes()),
 		flags
-	).unwrap().to_pem().unwrap();
+	)
+		.and_then(|cms| cms.to_pem())
+		.map_err(|err| CryptoError::Signing(err.to_string()))?;
+
+	if pem.len() < LEN_BEGIN + LEN_END {
+		return Err(CryptoError::Signing("OpenSSL devolvio un CMS vacio".to_owned()));
+	}
 
 	//remover cabezales
   pem.drain(pem.len() - LEN_END..);
 	pem.drain(0..LEN_BEGIN);
 
-	return String::from_utf8(pem).unwrap();
-}
\ No newline at end of file
+	return String::from_utf8(pem).map_err(|err| CryptoError::Signing(err.to_string()));
+}
+
+
+#[cfg(test)]
+mod tests {
+	use crate::types::errors::CryptoError;
+
+	#[test]
+	fn certificado_invalido() {
+		let res = super::sign_cms(b"no es un certificado", b"tampoco una llave", None, "<loginTicketRequest/>");
+		assert!(matches!(res, Err(CryptoError::InvalidCert(_))));
+	}
+}
@@ -0,0 +1,34 @@
+use crate::{crypto::sign_cms::sign_cms, types::errors::ErrType, wsaa::get_token::CertKeyPair};
+
+
+///Firma el `loginTicketRequest` que se envia a WSAA.
+///
+///`CertKeyPair` es la implementacion por defecto: firma con OpenSSL teniendo la llave privada en memoria.
+///Implementalo si la llave vive en un HSM o en un KMS y no puede salir de ahi (ver `Pkcs11Signer`).
+pub trait TraSigner: Send + Sync {
+	///CUIT del certificado con el que se firma
+	fn cuit(&self) -> i64;
+
+	///Firma `tra` como CMS SignedData, incluyendo el contenido y el certificado, y lo devuelve en base64 tal como lo espera `loginCms`
+	fn sign_tra(&self, tra:&str) -> Result<String, ErrType>;
+}
+
+impl TraSigner for CertKeyPair {
+	fn cuit(&self) -> i64 {
+		self.cuit
+	}
+
+	fn sign_tra(&self, tra:&str) -> Result<String, ErrType> {
+		Ok(sign_cms(&self.cert_contents, &self.key_contents, self.key_passphrase.as_deref(), tra)?)
+	}
+}
+
+impl TraSigner for Box<dyn TraSigner> {
+	fn cuit(&self) -> i64 {
+		self.as_ref().cuit()
+	}
+
+	fn sign_tra(&self, tra:&str) -> Result<String, ErrType> {
+		self.as_ref().sign_tra(tra)
+	}
+}
@@ -1,8 +1,19 @@
-#[cfg(feature = "qr_make")]
+#[cfg(feature = "wsaa")]
 mod crypto;
+#[cfg(feature = "wsaa")]
+pub use crypto::signer::TraSigner;
+#[cfg(feature = "pkcs11")]
+pub use crypto::pkcs11::Pkcs11Signer;
 
+#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
 mod xml_utils;
 pub mod types;
+#[cfg(any(feature = "wsaa", feature = "dummy_type", feature = "blocking"))]
+pub mod transport;
+#[cfg(any(feature = "wsaa", feature = "dummy_type", feature = "blocking"))]
+pub use transport::{SoapTransport, SoapRequest, SoapResponse, MemoryTransport, RecordingTransport, ReplayTransport, Fixture, TransportError, TransportErrorKind};
+#[cfg(any(feature = "wsaa", feature = "dummy_type", feature = "blocking"))]
+pub use types::enums::{ArcaEnvironment, CustomEnvironment, Webservice};
 pub mod wsfev1;
 pub mod wsfexv1;
 pub mod wsmtxca;
@@ -13,9 +24,38 @@ pub mod wsbfev1;
 #[cfg(feature = "qr_make")]
 pub mod qr_make;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+#[cfg(feature = "mock")]
+pub mod mock;
+
+#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
+mod retry;
+#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
+pub use retry::{RetryPolicy, RetryTransport, NON_IDEMPOTENT_OPERATIONS};
+#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
+mod circuit_breaker;
+#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
+pub use circuit_breaker::{CircuitBreaker, CircuitConfig, CircuitState};
+#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
+mod rate_limit;
+#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
+pub use rate_limit::{RateLimit, RateLimitMode, RateLimiter, RateLimitedTransport};
+
 #[cfg(feature = "wsaa")]
 mod wsaa;
 #[cfg(feature = "wsaa")]
-pub use wsaa::get_token::{ServiceId, TokenArca, CertKeyPair};
+pub use wsaa::get_token::{get_token, ServiceId, TokenArca, CertKeyPair};
+#[cfg(feature = "wsaa")]
+pub use wsaa::config::{WsaaConfig, AlreadyAuthPolicy};
+#[cfg(feature = "wsaa")]
+pub use wsaa::refresher::TokenRefresher;
+#[cfg(feature = "wsaa")]
+pub use wsaa::sso::{SsoTicket, SsoRelation};
+#[cfg(feature = "wsaa")]
+pub use wsaa::token_store::{TokenStore, FileTokenStore};
 #[cfg(feature = "wsaa")]
 pub use wsaa::validate_crt::*;
+#[cfg(feature = "wsaa")]
+pub use wsaa::generate_csr::*;
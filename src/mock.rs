@@ -0,0 +1,416 @@
+//! ARCA falso para tests: atiende `loginCms` de WSAA y `FEDummy`, `FECompUltimoAutorizado` y `FECAESolicitar` de WSFEv1.
+//! Se usa en memoria como `SoapTransport` o por HTTP con el binario `arca-mock`.
+//! Lleva la numeracion por CUIT, punto de venta y tipo de comprobante, y aplica las validaciones basicas de ARCA
+
+use std::{collections::{HashMap, VecDeque}, fmt, str::FromStr, sync::Mutex};
+
+use chrono::{DateTime, Days, Duration, FixedOffset, NaiveDate, Utc};
+
+use crate::{transport::{operation, SoapRequest, SoapResponse, SoapTransport, TransportError, TransportErrorKind}, types::enums::{ArcaEnvironment, CustomEnvironment, Webservice}, xml_utils::get_xml_vec};
+
+///Lo que dura un token emitido por el mock, igual que en ARCA
+const TOKEN_LIFETIME_HS: i64 = 12;
+///Dias de vigencia del CAE a partir de la fecha del comprobante
+const CAE_DIAS: u64 = 10;
+
+///Falla que devuelve el mock en el proximo pedido de una operacion, en lugar de la respuesta normal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockFault {
+	///`coe.alreadyAuthenticated` en `loginCms`
+	AlreadyAuthenticated,
+	///Rechaza el comprobante con la observacion 10016, como si la numeracion no fuera la esperada
+	Rejected10016,
+	///No responde. En memoria es un `TransportErrorKind::Timeout`; el binario deja la conexion abierta sin contestar
+	Timeout,
+	///SOAP fault con status 500
+	SoapFault { code: String, message: String },
+}
+
+///`already_authenticated`, `10016`, `timeout` o `soap_fault`, como se indican al binario
+impl FromStr for MockFault {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"already_authenticated" => Ok(MockFault::AlreadyAuthenticated),
+			"10016"                 => Ok(MockFault::Rejected10016),
+			"timeout"               => Ok(MockFault::Timeout),
+			"soap_fault"            => Ok(MockFault::SoapFault { code: "soap:Server".to_owned(), message: "Falla inyectada por arca-mock".to_owned() }),
+			other                   => Err(format!("Falla desconocida: {other}")),
+		}
+	}
+}
+
+impl fmt::Display for MockFault {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MockFault::AlreadyAuthenticated => write!(f, "already_authenticated"),
+			MockFault::Rejected10016        => write!(f, "10016"),
+			MockFault::Timeout              => write!(f, "timeout"),
+			MockFault::SoapFault { code, .. } => write!(f, "soap_fault ({code})"),
+		}
+	}
+}
+
+#[derive(Default)]
+struct Estado {
+	///Ultimo numero y fecha autorizados por (cuit, punto de venta, tipo de comprobante)
+	ultimos	: HashMap<(i64, i64, i64), (i64, NaiveDate)>,
+	///Sign y vencimiento de cada token emitido
+	tokens	: HashMap<String, (String, DateTime<Utc>)>,
+	fallas	: HashMap<String, VecDeque<MockFault>>,
+	logins	: u64,
+	caes		: u64,
+}
+
+///ARCA en memoria. Ver el modulo
+#[derive(Default)]
+pub struct ArcaMock {
+	estado: Mutex<Estado>,
+}
+
+impl ArcaMock {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	///Ambiente de homologacion con WSAA y WSFEv1 apuntando al mock servido en `base_url`, por ejemplo `http://127.0.0.1:8080`
+	pub fn environment(base_url: &str) -> ArcaEnvironment {
+		let base_url = base_url.trim_end_matches('/');
+		return ArcaEnvironment::Custom(CustomEnvironment::new("mock", false)
+			.with_url(Webservice::Wsaa, format!("{base_url}/ws/services/LoginCms"))
+			.with_url(Webservice::Wsfev1, format!("{base_url}/wsfev1/service.asmx")));
+	}
+
+	///Encola una falla para el proximo pedido de `operation` (`loginCms`, `FECAESolicitar`, etc.). Se consume al usarse
+	pub fn inject_fault(&self, operation: &str, fault: MockFault) {
+		self.estado().fallas.entry(operation.to_owned()).or_default().push_back(fault);
+	}
+
+	///Fallas de `operation` que todavia no se usaron
+	pub fn pending_faults(&self, operation: &str) -> usize {
+		return self.estado().fallas.get(operation).map_or(0, VecDeque::len);
+	}
+
+	///Fija el ultimo comprobante autorizado de una serie, como si ya se hubiera facturado antes
+	pub fn set_last_number(&self, cuit: i64, punto_venta: i64, tipo: i64, numero: i64, fecha: NaiveDate) {
+		self.estado().ultimos.insert((cuit, punto_venta, tipo), (numero, fecha));
+	}
+
+	///Responde un pedido. `None` si hay que simular un timeout
+	pub fn handle(&self, req: &SoapRequest) -> Option<SoapResponse> {
+		let operation = operation(req);
+		let soap12 = req.content_type.contains("soap+xml");
+		let mut estado = self.estado();
+		let falla = estado.fallas.get_mut(&operation).and_then(VecDeque::pop_front);
+
+		let (status, body) = match (operation.as_str(), falla) {
+			(_, Some(MockFault::Timeout)) => return None,
+			(_, Some(MockFault::SoapFault { code, message })) => (500, fault(&code, &message)),
+			("loginCms", Some(MockFault::AlreadyAuthenticated)) => (500, fault("ns1:coe.alreadyAuthenticated", "El CEE ya posee un TA valido para el acceso al WSN solicitado")),
+			("loginCms", _) => (200, estado.login()),
+			("FEDummy", _) => (200, DUMMY_OK.to_owned()),
+			("FECompUltimoAutorizado", _) => (200, estado.ultimo_autorizado(&req.body)),
+			("FECAESolicitar", falla) => (200, estado.solicitar_cae(&req.body, falla == Some(MockFault::Rejected10016))),
+			(otra, _) => (500, fault("soap:Client", &format!("Operacion no soportada por arca-mock: {otra}"))),
+		};
+
+		let content_type = if soap12 {"application/soap+xml; charset=utf-8"} else {"text/xml; charset=utf-8"};
+		return Some(SoapResponse {
+			status,
+			headers	: vec![("content-type".to_owned(), content_type.to_owned())],
+			body		: sobre(soap12, &body),
+		});
+	}
+
+	fn estado(&self) -> std::sync::MutexGuard<'_, Estado> {
+		self.estado.lock().unwrap_or_else(|err| err.into_inner())
+	}
+}
+
+impl SoapTransport for ArcaMock {
+	async fn send(&self, req: SoapRequest) -> Result<SoapResponse, TransportError> {
+		return self.handle(&req).ok_or_else(|| TransportError::new(TransportErrorKind::Timeout, "arca-mock no respondio (falla inyectada)"));
+	}
+}
+
+
+impl Estado {
+	fn login(&mut self) -> String {
+		self.logins += 1;
+		let ahora = ahora();
+		let expira = ahora + Duration::hours(TOKEN_LIFETIME_HS);
+		let (token, sign) = (format!("mock-token-{}", self.logins), format!("mock-sign-{}", self.logins));
+		self.tokens.insert(token.clone(), (sign.clone(), expira.to_utc()));
+
+		let ticket = format!(r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<loginTicketResponse version="1.0">
+    <header>
+        <source>CN=wsaahomo, O=AFIP, C=AR, SERIALNUMBER=CUIT 33693450239</source>
+        <destination>CN=arca-mock</destination>
+        <uniqueId>{}</uniqueId>
+        <generationTime>{}</generationTime>
+        <expirationTime>{}</expirationTime>
+    </header>
+    <credentials>
+        <token>{token}</token>
+        <sign>{sign}</sign>
+    </credentials>
+</loginTicketResponse>"#, self.logins, ahora.to_rfc3339(), expira.to_rfc3339());
+		let ticket = ticket.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+		return format!(r#"<loginCmsResponse xmlns="http://wsaa.view.sua.dvadac.desein.afip.gov"><loginCmsReturn>{ticket}</loginCmsReturn></loginCmsResponse>"#);
+	}
+
+	///Error 600 si el token y el sign no son de un login vigente
+	fn validar_auth(&self, body: &str) -> Option<(i64, &'static str)> {
+		let (token, sign) = (campo(body, "Token").unwrap_or_default(), campo(body, "Sign").unwrap_or_default());
+		match self.tokens.get(&token) {
+			Some((esperado, vence)) if *esperado == sign && *vence > Utc::now() => None,
+			Some((esperado, _)) if *esperado == sign => Some((600, "ValidacionDeToken: No validaron las fechas del token GenTime, ExpTime, NowUTC")),
+			_ => Some((600, "ValidacionDeToken: No se corresponden token con firma")),
+		}
+	}
+
+	fn ultimo_autorizado(&self, body: &str) -> String {
+		let (punto_venta, tipo) = (numero(body, "PtoVta"), numero(body, "CbteTipo"));
+		let resultado = match self.validar_auth(body) {
+			Some(err) => errores(&[err]),
+			None => {
+				let (ultimo, _) = self.ultimos.get(&(numero(body, "Cuit"), punto_venta, tipo)).copied().unwrap_or((0, NaiveDate::MIN));
+				format!("<PtoVta>{punto_venta}</PtoVta><CbteTipo>{tipo}</CbteTipo><CbteNro>{ultimo}</CbteNro>")
+			},
+		};
+		return format!(r#"<FECompUltimoAutorizadoResponse xmlns="http://ar.gov.afip.dif.FEV1/"><FECompUltimoAutorizadoResult>{resultado}</FECompUltimoAutorizadoResult></FECompUltimoAutorizadoResponse>"#);
+	}
+
+	fn solicitar_cae(&mut self, body: &str, rechazar: bool) -> String {
+		let envolver = |resultado: String| format!(r#"<FECAESolicitarResponse xmlns="http://ar.gov.afip.dif.FEV1/"><FECAESolicitarResult>{resultado}</FECAESolicitarResult></FECAESolicitarResponse>"#);
+		if let Some(err) = self.validar_auth(body) {
+			return envolver(errores(&[err]));
+		}
+
+		let cuit = numero(body, "Cuit");
+		let (punto_venta, tipo) = (numero(body, "PtoVta"), numero(body, "CbteTipo"));
+		let detalles = campos(body, "FECAEDetRequest");
+		if detalles.is_empty() || numero(body, "CantReg") != detalles.len() as i64 {
+			return envolver(errores(&[(10001, "La cantidad de registros informada en CantReg no coincide con la de FECAEDetRequest")]));
+		}
+
+		let mut det_resp = String::new();
+		let mut aprobados = 0;
+		for det in &detalles {
+			let desde = numero(det, "CbteDesde");
+			let fecha = NaiveDate::parse_from_str(&campo(det, "CbteFch").unwrap_or_default(), "%Y%m%d").unwrap_or(ahora().date_naive());
+			let (ultimo, fecha_ultimo) = self.ultimos.get(&(cuit, punto_venta, tipo)).copied().unwrap_or((0, NaiveDate::MIN));
+			let suma = ["ImpTotConc", "ImpNeto", "ImpOpEx", "ImpTrib", "ImpIVA"].iter().map(|tag| importe(det, tag)).sum::<f64>();
+
+			let mut obs = Vec::new();
+			if rechazar || desde != ultimo + 1 || fecha < fecha_ultimo {
+				obs.push((10016, "El numero o fecha del comprobante no se corresponde con el proximo a autorizar. Consultar metodo FECompUltimoAutorizado."));
+			}
+			if numero(det, "CbteHasta") != desde {
+				obs.push((10011, "Los campos CbteDesde y CbteHasta deben ser iguales"));
+			}
+			if (importe(det, "ImpTotal") - suma).abs() > 0.01 {
+				obs.push((10048, "El campo 'Importe Total' ImpTotal, debe ser igual a la suma de ImpTotConc + ImpNeto + ImpOpEx + ImpTrib + ImpIVA."));
+			}
+
+			let (resultado, cae) = if obs.is_empty() {
+				self.caes += 1;
+				aprobados += 1;
+				self.ultimos.insert((cuit, punto_venta, tipo), (desde, fecha));
+				let vto = fecha.checked_add_days(Days::new(CAE_DIAS)).unwrap_or(fecha);
+				("A", format!("<CAE>7{:013}</CAE><CAEFchVto>{}</CAEFchVto>", self.caes, vto.format("%Y%m%d")))
+			} else {
+				("R", "<CAE></CAE><CAEFchVto></CAEFchVto>".to_owned())
+			};
+			let observaciones = if obs.is_empty() {String::new()} else {
+				format!("<Observaciones>{}</Observaciones>", obs.iter().map(|(code, msg)| format!("<Obs><Code>{code}</Code><Msg>{msg}</Msg></Obs>")).collect::<String>())
+			};
+			det_resp += &format!(
+				"<FECAEDetResponse><Concepto>{}</Concepto><DocTipo>{}</DocTipo><DocNro>{}</DocNro><CbteDesde>{desde}</CbteDesde><CbteHasta>{desde}</CbteHasta><CbteFch>{}</CbteFch><Resultado>{resultado}</Resultado>{observaciones}{cae}</FECAEDetResponse>",
+				numero(det, "Concepto"), numero(det, "DocTipo"), numero(det, "DocNro"), fecha.format("%Y%m%d"),
+			);
+		}
+
+		let resultado = match aprobados {
+			0 => "R",
+			n if n == detalles.len() => "A",
+			_ => "P",
+		};
+		return envolver(format!(
+			"<FeCabResp><Cuit>{cuit}</Cuit><PtoVta>{punto_venta}</PtoVta><CbteTipo>{tipo}</CbteTipo><FchProceso>{}</FchProceso><CantReg>{}</CantReg><Resultado>{resultado}</Resultado><Reproceso>N</Reproceso></FeCabResp><FeDetResp>{det_resp}</FeDetResp>",
+			ahora().format("%Y%m%d%H%M%S"), detalles.len(),
+		));
+	}
+}
+
+
+const DUMMY_OK: &str = r#"<FEDummyResponse xmlns="http://ar.gov.afip.dif.FEV1/"><FEDummyResult><AppServer>OK</AppServer><DbServer>OK</DbServer><AuthServer>OK</AuthServer></FEDummyResult></FEDummyResponse>"#;
+
+///Hora de Argentina, como la informa ARCA
+fn ahora() -> DateTime<FixedOffset> {
+	let offset = FixedOffset::west_opt(3 * 3600).expect("offset valido");
+	return Utc::now().with_timezone(&offset);
+}
+
+fn sobre(soap12: bool, body: &str) -> String {
+	let ns = if soap12 {"http://www.w3.org/2003/05/soap-envelope"} else {"http://schemas.xmlsoap.org/soap/envelope/"};
+	return format!(r#"<?xml version="1.0" encoding="utf-8"?><soap:Envelope xmlns:soap="{ns}"><soap:Body>{body}</soap:Body></soap:Envelope>"#);
+}
+
+fn fault(code: &str, message: &str) -> String {
+	return format!("<soap:Fault><faultcode>{code}</faultcode><faultstring>{message}</faultstring></soap:Fault>");
+}
+
+fn errores(errores: &[(i64, &str)]) -> String {
+	let errores: String = errores.iter().map(|(code, msg)| format!("<Err><Code>{code}</Code><Msg>{msg}</Msg></Err>")).collect();
+	return format!("<Errors>{errores}</Errors>");
+}
+
+///Los clientes pueden usar el prefijo `ar:` o el namespace por defecto
+fn campos(xml: &str, tag: &str) -> Vec<String> {
+	let con_prefijo = get_xml_vec(xml, &format!("ar:{tag}"));
+	return if con_prefijo.is_empty() {get_xml_vec(xml, tag)} else {con_prefijo};
+}
+
+fn campo(xml: &str, tag: &str) -> Option<String> {
+	return campos(xml, tag).into_iter().next().map(|valor| valor.trim().to_owned());
+}
+
+fn numero(xml: &str, tag: &str) -> i64 {
+	return campo(xml, tag).and_then(|valor| valor.parse().ok()).unwrap_or_default();
+}
+
+fn importe(xml: &str, tag: &str) -> f64 {
+	return campo(xml, tag).and_then(|valor| valor.parse().ok()).unwrap_or_default();
+}
+
+
+///Certificado y comprobante para los tests que facturan contra el mock
+#[cfg(all(test, feature = "wsfev1_fe_cae_solicitar"))]
+pub(crate) mod de_prueba {
+	use chrono::NaiveDate;
+	use openssl::{asn1::Asn1Time, hash::MessageDigest, pkey::PKey, rsa::Rsa, x509::{X509NameBuilder, X509}};
+
+	use crate::{wsaa::get_token::CertKeyPair, wsfev1::fe_cae_solicitar::generar_request::*};
+
+	pub(crate) const CUIT: i64 = 20111111112;
+
+	///Autofirmado: el mock no verifica la cadena
+	pub(crate) fn cert_key_pair() -> CertKeyPair {
+		let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+		let mut name = X509NameBuilder::new().unwrap();
+		name.append_entry_by_text("CN", "facturador").unwrap();
+		let name = name.build();
+		let mut cert = X509::builder().unwrap();
+		cert.set_subject_name(&name).unwrap();
+		cert.set_issuer_name(&name).unwrap();
+		cert.set_pubkey(&key).unwrap();
+		cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+		cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+		cert.sign(&key, MessageDigest::sha256()).unwrap();
+		return CertKeyPair {
+			cuit: CUIT, cert_contents: cert.build().to_pem().unwrap(), key_contents: key.private_key_to_pem_pkcs8().unwrap(), key_passphrase: None,
+		};
+	}
+
+	///Factura B por 121 en el punto de venta 3
+	pub(crate) fn comprobante(numero: i64) -> Comprobante {
+		return Comprobante {
+			id_factura: 1,
+			cabezal: ComprobCabezal {
+				punto_venta: 3, num_documento: numero, tipo_rg1415: 6, concepto: 1, fecha_emision: NaiveDate::from_ymd_opt(2026, 10, 18).unwrap(),
+				moneda: "PES".to_owned(), cotizacion: 1.0, cancela_misma_moneda: false, servicio_desde: None, servicio_hasta: None, venci_pago: None,
+			},
+			cliente: ComprobCliente { tipo_doc: 99, documento: 0, cond_iva: 5 },
+			valores: ComprobValores {
+				val_total: 121.0, val_nogravado: 0.0, val_gravado: 100.0, val_exento: 0.0, val_iva: 21.0, val_otros_trib: 0.0,
+				tributos: None, alicuotas_iva: Some(vec![ComprobIVA { id_alicuota: 5, base: 100.0, importe: 21.0 }]),
+			},
+			comprob_asociados: None, periodo_asociado: None, opcionales: None, actividades: None,
+		};
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn pedido(body: &str) -> SoapRequest {
+		SoapRequest {
+			url						: "http://127.0.0.1/wsfev1/service.asmx".to_owned(),
+			soap_action		: None,
+			content_type	: "application/soap+xml".to_owned(),
+			body					: format!("<soap:Body>{body}</soap:Body>"),
+			timeout				: None,
+			cuit					: None,
+			webservice		: None,
+		}
+	}
+
+	fn factura(mock: &ArcaMock, token: &str, sign: &str, numero: i64) -> String {
+		let body = format!("<ar:FECAESolicitar><ar:Auth><ar:Token>{token}</ar:Token><ar:Sign>{sign}</ar:Sign><ar:Cuit>20111111112</ar:Cuit></ar:Auth>\
+			<ar:FeCAEReq><ar:FeCabReq><ar:CantReg>1</ar:CantReg><ar:PtoVta>3</ar:PtoVta><ar:CbteTipo>6</ar:CbteTipo></ar:FeCabReq>\
+			<ar:FeDetReq><ar:FECAEDetRequest><ar:CbteDesde>{numero}</ar:CbteDesde><ar:CbteHasta>{numero}</ar:CbteHasta><ar:CbteFch>20261018</ar:CbteFch>\
+			<ar:ImpTotal>121</ar:ImpTotal><ar:ImpNeto>100</ar:ImpNeto><ar:ImpIVA>21</ar:ImpIVA></ar:FECAEDetRequest></ar:FeDetReq></ar:FeCAEReq></ar:FECAESolicitar>");
+		return mock.handle(&pedido(&body)).unwrap().body;
+	}
+
+	#[test]
+	fn numeracion_y_fallas() {
+		let mock = ArcaMock::new();
+		let login = mock.handle(&pedido("<wsaa:loginCms><wsaa:in0>cms</wsaa:in0></wsaa:loginCms>")).unwrap().body;
+		assert!(login.contains("&lt;token&gt;mock-token-1&lt;/token&gt;"));
+
+		assert!(factura(&mock, "mock-token-1", "otro", 1).contains("<Code>600</Code>"));
+		assert!(factura(&mock, "mock-token-1", "mock-sign-1", 1).contains("<CAE>70000000000001</CAE>"));
+		assert!(factura(&mock, "mock-token-1", "mock-sign-1", 1).contains("<Code>10016</Code>"));
+
+		mock.inject_fault("FECAESolicitar", MockFault::Rejected10016);
+		assert!(factura(&mock, "mock-token-1", "mock-sign-1", 2).contains("<Code>10016</Code>"));
+		assert!(factura(&mock, "mock-token-1", "mock-sign-1", 2).contains("<Resultado>A</Resultado>"));
+
+		let ultimo = mock.handle(&pedido("<ar:FECompUltimoAutorizado><ar:Auth><ar:Token>mock-token-1</ar:Token><ar:Sign>mock-sign-1</ar:Sign><ar:Cuit>20111111112</ar:Cuit></ar:Auth><ar:PtoVta>3</ar:PtoVta><ar:CbteTipo>6</ar:CbteTipo></ar:FECompUltimoAutorizado>")).unwrap();
+		assert!(ultimo.body.contains("<CbteNro>2</CbteNro>"));
+
+		mock.inject_fault("FEDummy", MockFault::Timeout);
+		assert!(mock.handle(&pedido("<FEDummy/>")).is_none());
+		assert_eq!(mock.handle(&pedido("<FEDummy/>")).unwrap().status, 200);
+	}
+
+	#[cfg(feature = "wsfev1_fe_cae_solicitar")]
+	#[tokio::test]
+	async fn factura_completa() {
+		use dashmap::DashMap;
+		use crate::{types::errors::ErrType, wsaa::config::{AlreadyAuthPolicy, WsaaConfig}, wsfev1::fe_cae_solicitar::{generar_request::generar_request, parse_response::parse_response}};
+		use super::de_prueba::{cert_key_pair, comprobante, CUIT};
+
+		let mock = ArcaMock::new();
+		mock.set_last_number(CUIT, 3, 6, 41, NaiveDate::from_ymd_opt(2026, 10, 1).unwrap());
+		let environment = ArcaMock::environment("http://127.0.0.1:8080");
+		let facturar = async |store: &DashMap<_, _>, config: &WsaaConfig, numero| {
+			let req = generar_request(store, 1, &environment, None, &mock, config, &comprobante(numero), async |_| Some(cert_key_pair())).await?;
+			let res = mock.send(req).await?;
+			return parse_response(&res.body, res.status);
+		};
+
+		let store = DashMap::new();
+		assert_eq!(facturar(&store, &WsaaConfig::default(), 42).await.unwrap().cae, "70000000000001");
+		assert!(matches!(facturar(&store, &WsaaConfig::default(), 42).await, Err(ErrType::Soap(fault)) if fault.fault_code.as_deref() == Some("10016")));
+
+		//Con un store nuevo hay que volver a hacer el login, y ARCA todavia tiene vigente el token anterior
+		mock.inject_fault("loginCms", MockFault::AlreadyAuthenticated);
+		assert!(matches!(facturar(&DashMap::new(), &WsaaConfig::default(), 43).await, Err(ErrType::AlreadyAuthenticated { .. })));
+		assert_eq!(mock.pending_faults("loginCms"), 0);
+
+		mock.inject_fault("loginCms", MockFault::AlreadyAuthenticated);
+		let reintenta = WsaaConfig {
+			already_auth: AlreadyAuthPolicy::Retry { interval: std::time::Duration::from_millis(1), max_wait: std::time::Duration::from_secs(1) },
+			..WsaaConfig::default()
+		};
+		assert_eq!(facturar(&DashMap::new(), &reintenta, 43).await.unwrap().cae, "70000000000002");
+		assert_eq!(mock.pending_faults("loginCms"), 0);
+	}
+}
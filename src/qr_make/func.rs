@@ -8,7 +8,7 @@ use serde::Serialize;
 const QR_ARCA_URL:&str = "https://www.arca.gob.ar/fe/qr/?p=";
 
 pub fn qr_make_file(json:&FacJson, to_file_path:PathBuf){
-	let mut path = PathBuf::from(to_file_path);
+	let mut path = to_file_path;
 	let qr_str = qr_make_url(json);
 	let size = 200;
 	path.set_extension("svg");
@@ -0,0 +1,209 @@
+use std::{collections::HashMap, future::Future, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use crate::{transport::{SoapRequest, SoapResponse, SoapTransport, TransportError, TransportErrorKind}, types::enums::Webservice};
+
+///CUIT representado y servicio
+type Clave = (Option<i64>, Option<Webservice>);
+
+///Token bucket: admite rafagas de hasta `burst` pedidos y despues uno cada `interval`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+	pub burst		: u32,
+	pub interval: Duration,
+}
+
+impl RateLimit {
+	pub fn per_second(pedidos:u32, burst:u32) -> Self {
+		Self { burst, interval: Duration::from_secs(1) / pedidos.max(1) }
+	}
+
+	pub fn per_minute(pedidos:u32, burst:u32) -> Self {
+		Self { burst, interval: Duration::from_secs(60) / pedidos.max(1) }
+	}
+}
+
+///Que hacer con un pedido que se pasa del limite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLimitMode {
+	///Esperar a que haya lugar. Los pedidos salen en el orden en que llegaron
+	#[default]
+	Wait,
+	///Fallar al instante con `TransportErrorKind::RateLimited`
+	FailFast,
+}
+
+struct Balde {
+	disponibles	: f64,
+	ultimo			: Instant,
+}
+
+///Limites de pedidos a ARCA por CUIT representado y servicio, para no quedar bloqueado por exceso de pedidos.
+///
+///Cada combinacion de `SoapRequest::cuit` y `SoapRequest::webservice` tiene su propio balde. Los pedidos sin CUIT, como los dummy,
+///comparten uno por servicio. Los servicios sin limite propio usan el de `with_default`; si tampoco hay, no se limitan
+pub struct RateLimiter {
+	mode			: RateLimitMode,
+	default		: Option<RateLimit>,
+	limits		: HashMap<Webservice, RateLimit>,
+	baldes		: Mutex<HashMap<Clave, Balde>>,
+}
+
+impl RateLimiter {
+	pub fn new(mode:RateLimitMode) -> Self {
+		RateLimiter { mode, default: None, limits: HashMap::new(), baldes: Mutex::new(HashMap::new()) }
+	}
+
+	pub fn with_default(mut self, limit:RateLimit) -> Self {
+		self.default = Some(limit);
+		self
+	}
+
+	pub fn with_limit(mut self, webservice:Webservice, limit:RateLimit) -> Self {
+		self.limits.insert(webservice, limit);
+		self
+	}
+
+	///Toma un lugar para el pedido. Devuelve cuanto hay que esperar antes de enviarlo, o `Err` con la espera si el modo es `FailFast`
+	pub fn acquire(&self, cuit:Option<i64>, webservice:Option<&Webservice>) -> Result<Duration, Duration> {
+		let Some(limit) = webservice.and_then(|ws| self.limits.get(ws)).or(self.default.as_ref()) else { return Ok(Duration::ZERO) };
+		let ahora = Instant::now();
+		let mut baldes = self.baldes.lock().unwrap_or_else(|err| err.into_inner());
+		let balde = baldes.entry((cuit, webservice.cloned())).or_insert(Balde { disponibles: limit.burst as f64, ultimo: ahora });
+
+		let intervalo = limit.interval.as_secs_f64().max(f64::EPSILON);
+		balde.disponibles = (balde.disponibles + (ahora - balde.ultimo).as_secs_f64() / intervalo).min(limit.burst.max(1) as f64);
+		balde.ultimo = ahora;
+
+		let espera = Duration::from_secs_f64((1.0 - balde.disponibles).max(0.0) * intervalo);
+		if espera > Duration::ZERO && self.mode == RateLimitMode::FailFast {
+			return Err(espera);
+		}
+		//En modo Wait el lugar queda reservado, asi que el balde puede quedar en negativo
+		balde.disponibles -= 1.0;
+		return Ok(espera);
+	}
+}
+
+///Envia con `inner` respetando los limites de `limiter`. Conviene compartir el mismo `RateLimiter` (con `Arc`) entre todos los
+///transportes que usen los mismos CUITs, ya que todo pedido a ARCA pasa por un `SoapTransport`: `auth_arca`, los de `generar_request`
+///y los `service_status`.
+///
+///En modo `Wait` espera con `tokio::time::sleep`. Sin runtime de tokio, como con `crate::blocking`, hay que cambiarlo con
+///`with_sleep(blocking::sleep)`
+pub struct RateLimitedTransport<T, D = fn(Duration) -> tokio::time::Sleep> {
+	inner		: T,
+	limiter	: Arc<RateLimiter>,
+	dormir	: D,
+}
+
+impl<T: SoapTransport> RateLimitedTransport<T> {
+	pub fn new(inner:T, limiter:Arc<RateLimiter>) -> Self {
+		RateLimitedTransport { inner, limiter, dormir: tokio::time::sleep }
+	}
+}
+
+impl<T, D> RateLimitedTransport<T, D> {
+	///Cambia la espera del modo `Wait`
+	pub fn with_sleep<E, F>(self, dormir:E) -> RateLimitedTransport<T, E>
+	where
+		E: Fn(Duration) -> F + Send + Sync,
+		F: Future<Output = ()> + Send,
+	{
+		RateLimitedTransport { inner: self.inner, limiter: self.limiter, dormir }
+	}
+}
+
+impl<T, D, F> SoapTransport for RateLimitedTransport<T, D>
+where
+	T: SoapTransport,
+	D: Fn(Duration) -> F + Send + Sync,
+	F: Future<Output = ()> + Send,
+{
+	async fn send(&self, req:SoapRequest) -> Result<SoapResponse, TransportError> {
+		match self.limiter.acquire(req.cuit, req.webservice.as_ref()) {
+			Ok(Duration::ZERO) => {},
+			Ok(espera) => (self.dormir)(espera).await,
+			Err(espera) => return Err(TransportError::new(
+				TransportErrorKind::RateLimited,
+				format!("Limite de pedidos alcanzado para {:?} en {}. Hay lugar en {espera:?}", req.cuit, req.webservice.as_ref().map_or("?", Webservice::service_name)),
+			)),
+		}
+		return self.inner.send(req).await;
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn baldes_por_cuit_y_servicio() {
+		let limiter = RateLimiter::new(RateLimitMode::FailFast).with_limit(Webservice::Wsfev1, RateLimit::per_minute(1, 2));
+		let wsfe = Some(&Webservice::Wsfev1);
+		assert_eq!(limiter.acquire(Some(1), wsfe), Ok(Duration::ZERO));
+		assert_eq!(limiter.acquire(Some(1), wsfe), Ok(Duration::ZERO));
+		assert!(limiter.acquire(Some(1), wsfe).unwrap_err() > Duration::from_secs(59));
+		assert_eq!(limiter.acquire(Some(2), wsfe), Ok(Duration::ZERO));
+		assert_eq!(limiter.acquire(Some(1), Some(&Webservice::Wsaa)), Ok(Duration::ZERO));
+
+		let espera = RateLimiter::new(RateLimitMode::Wait).with_default(RateLimit::per_second(10, 1));
+		assert_eq!(espera.acquire(None, wsfe), Ok(Duration::ZERO));
+		assert!(espera.acquire(None, wsfe).unwrap() > Duration::from_millis(90));
+		assert!(espera.acquire(None, wsfe).unwrap() > Duration::from_millis(190));
+	}
+
+	#[tokio::test]
+	async fn falla_sin_enviar() {
+		let transport = RateLimitedTransport::new(
+			crate::transport::MemoryTransport::new(|_| Ok(SoapResponse::ok(String::new()))),
+			Arc::new(RateLimiter::new(RateLimitMode::FailFast).with_default(RateLimit::per_minute(1, 1))),
+		);
+		let pedido = SoapRequest {
+			url						: "http://127.0.0.1/ws/services/LoginCms".to_owned(),
+			soap_action		: Some(String::new()),
+			content_type	: "text/xml".to_owned(),
+			body					: String::new(),
+			timeout				: None,
+			cuit					: Some(20111111112),
+			webservice		: Some(Webservice::Wsaa),
+		};
+
+		assert!(transport.send(pedido.clone()).await.is_ok());
+		assert_eq!(transport.send(pedido).await.unwrap_err().kind, TransportErrorKind::RateLimited);
+		assert_eq!(transport.inner.requests().len(), 1);
+	}
+
+	///`get_token`, `generar_request` y `service_status` comparten el limiter si usan el mismo transporte,
+	///y WSAA y WSFEv1 tienen baldes separados para el mismo CUIT
+	#[cfg(all(feature = "mock", feature = "wsfev1_fe_cae_solicitar"))]
+	#[tokio::test]
+	async fn un_limiter_para_todo_el_circuito() {
+		use dashmap::DashMap;
+		use crate::{mock::{de_prueba::{cert_key_pair, comprobante, CUIT}, ArcaMock}, wsaa::{config::WsaaConfig, get_token::{get_token, ServiceId}}, wsfev1::fe_cae_solicitar::generar_request::generar_request};
+
+		let transport = RateLimitedTransport::new(
+			ArcaMock::new(),
+			Arc::new(RateLimiter::new(RateLimitMode::FailFast).with_default(RateLimit::per_minute(1, 1))),
+		);
+		let environment = ArcaMock::environment("http://127.0.0.1:8080");
+		let store = DashMap::new();
+		let config = WsaaConfig::default();
+		let key = ServiceId::new(1, Webservice::Wsfev1, environment.clone());
+
+		let token = get_token(&store, key, &transport, &config, async |_| Some(cert_key_pair()), |_, cuit, _, _| cuit.to_string()).await;
+		assert_eq!(token.unwrap(), CUIT.to_string());
+		assert!(transport.limiter.acquire(Some(CUIT), Some(&Webservice::Wsaa)).is_err());
+
+		//El token ya esta en el store: el unico pedido a WSAA fue el de get_token, y WSFEv1 tiene su propio balde
+		let req = generar_request(&store, 1, &environment, None, &transport, &config, &comprobante(1), async |_| Some(cert_key_pair())).await.unwrap();
+		assert_eq!((req.cuit, req.webservice.as_ref()), (Some(CUIT), Some(&Webservice::Wsfev1)));
+		assert_eq!(transport.send(req).await.unwrap().status, 200);
+
+		//Los dummy no tienen CUIT
+		assert_eq!(crate::wsfev1::service_status(&transport, &environment, None).await.status, 200);
+
+		let req = generar_request(&store, 1, &environment, None, &transport, &config, &comprobante(2), async |_| Some(cert_key_pair())).await.unwrap();
+		assert_eq!(transport.send(req).await.unwrap_err().kind, TransportErrorKind::RateLimited);
+	}
+}
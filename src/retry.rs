@@ -0,0 +1,207 @@
+use std::{collections::hash_map::RandomState, future::Future, hash::BuildHasher, time::{Duration, Instant}};
+
+use crate::{transport::{operation, SoapRequest, SoapResponse, SoapTransport, TransportError, TransportErrorKind}, types::errors::{ErrType, ErrorClass}};
+
+///Operaciones que no se pueden reenviar a ciegas: si no se sabe si ARCA las proceso, reenviarlas puede autorizar dos veces el mismo comprobante
+pub const NON_IDEMPOTENT_OPERATIONS: [&str; 3] = ["FECAESolicitar", "FEXAuthorize", "autorizarComprobante"];
+
+///Cuantas veces y cada cuanto se reintenta un pedido que fallo.
+///
+///Solo se reintentan los errores `ErrorClass::Transport`, y los `ErrorClass::OutcomeUnknown` si la operacion es idempotente.
+///Los SOAP faults y los rechazos de ARCA nunca se reintentan
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+	///Intentos en total, contando el primero. Con 1 no se reintenta
+	pub max_attempts	: u32,
+	///Demora antes del primer reintento. Se duplica en cada uno
+	pub base_delay		: Duration,
+	pub max_delay			: Duration,
+	///Hasta cuanto se suma al azar a cada demora, en porcentaje, para no sincronizar los reintentos de varios procesos
+	pub jitter_percent: u32,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self {
+			max_attempts	: 3,
+			base_delay		: Duration::from_millis(500),
+			max_delay			: Duration::from_secs(10),
+			jitter_percent: 20,
+		}
+	}
+}
+
+impl RetryPolicy {
+	///Sin reintentos
+	pub fn none() -> Self {
+		Self { max_attempts: 1, ..Self::default() }
+	}
+
+	///Cuanto esperar antes del intento `attempt + 1`, o `None` si no hay que reintentar
+	pub fn retry_delay(&self, class:ErrorClass, attempt:u32, idempotent:bool) -> Option<Duration> {
+		let reintentable = match class {
+			ErrorClass::Transport      => true,
+			ErrorClass::OutcomeUnknown => idempotent,
+			ErrorClass::SoapFault | ErrorClass::Rejection | ErrorClass::Local => false,
+		};
+		if !reintentable || attempt >= self.max_attempts {
+			return None;
+		}
+		let base = self.base_delay.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(self.max_delay);
+		let azar = RandomState::new().hash_one(Instant::now()) % 1000;
+		return Some(base + base.mul_f64((azar * self.jitter_percent as u64) as f64 / 100_000.0));
+	}
+
+	///Ejecuta `op` hasta que funcione o la politica diga que no se reintenta. Devuelve el ultimo error.
+	///`idempotent` indica si es seguro repetir `op` cuando no se sabe si ARCA la proceso.
+	///`dormir` hace la espera entre intentos: `tokio::time::sleep`, o `blocking::sleep` sin runtime
+	pub async fn run<R>(&self, idempotent:bool, dormir: impl AsyncFn(Duration), mut op: impl AsyncFnMut() -> Result<R, ErrType>) -> Result<R, ErrType> {
+		let mut intento = 1;
+		loop {
+			match op().await {
+				Ok(res) => return Ok(res),
+				Err(err) => {
+					let Some(demora) = self.retry_delay(err.class(), intento, idempotent) else { return Err(err) };
+					tracing::warn!(intento, error = ?err, "Reintentando pedido a ARCA en {demora:?}");
+					dormir(demora).await;
+					intento += 1;
+				},
+			}
+		}
+	}
+}
+
+///Envia con `inner` reintentando segun `policy`. Las operaciones de `NON_IDEMPOTENT_OPERATIONS` solo se reintentan si el pedido
+///seguro no llego a ARCA (`TransportErrorKind::Connect`); ante un timeout devuelven el error para que se consulte el resultado,
+///por ejemplo con `FECompUltimoAutorizado`.
+///
+///Tambien se reintentan las respuestas 502, 503 y 504 sin SOAP fault, que suelen venir de un proxy o balanceador.
+///
+///Entre intentos espera con `tokio::time::sleep`. Sin runtime de tokio, como con `crate::blocking`, hay que cambiarlo con
+///`with_sleep(blocking::sleep)`
+pub struct RetryTransport<T, D = fn(Duration) -> tokio::time::Sleep> {
+	inner	: T,
+	policy: RetryPolicy,
+	dormir: D,
+}
+
+impl<T: SoapTransport> RetryTransport<T> {
+	pub fn new(inner:T, policy:RetryPolicy) -> Self {
+		RetryTransport { inner, policy, dormir: tokio::time::sleep }
+	}
+}
+
+impl<T, D> RetryTransport<T, D> {
+	///Cambia la espera entre intentos
+	pub fn with_sleep<E, F>(self, dormir:E) -> RetryTransport<T, E>
+	where
+		E: Fn(Duration) -> F + Send + Sync,
+		F: Future<Output = ()> + Send,
+	{
+		RetryTransport { inner: self.inner, policy: self.policy, dormir }
+	}
+}
+
+impl<T, D, F> SoapTransport for RetryTransport<T, D>
+where
+	T: SoapTransport,
+	D: Fn(Duration) -> F + Send + Sync,
+	F: Future<Output = ()> + Send,
+{
+	async fn send(&self, req:SoapRequest) -> Result<SoapResponse, TransportError> {
+		return send_with_retry(&self.inner, req, &self.policy, &self.dormir).await;
+	}
+}
+
+async fn send_with_retry<T, D, F>(transport:&T, req:SoapRequest, policy:&RetryPolicy, dormir:&D) -> Result<SoapResponse, TransportError>
+where
+	T: SoapTransport,
+	D: Fn(Duration) -> F,
+	F: Future<Output = ()>,
+{
+	let idempotent = !NON_IDEMPOTENT_OPERATIONS.contains(&operation(&req).as_str());
+	let mut intento = 1;
+	loop {
+		let err = match transport.send(req.clone()).await {
+			Ok(res) if matches!(res.status, 502..=504) && !res.body.contains("Fault") => {
+				TransportError::new(TransportErrorKind::Other, format!("ARCA respondio {} sin SOAP fault", res.status))
+			},
+			Ok(res) => return Ok(res),
+			Err(err) => err,
+		};
+		let Some(demora) = policy.retry_delay(ErrorClass::of_transport(err.kind), intento, idempotent) else { return Err(err) };
+		tracing::warn!(intento, error = %err, url = req.url, "Reintentando pedido a ARCA en {demora:?}");
+		dormir(demora).await;
+		intento += 1;
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::{AtomicU32, Ordering};
+
+	use crate::{transport::MemoryTransport, types::errors::SoapFault};
+	#[cfg(feature = "mock")]
+	use crate::mock::{ArcaMock, MockFault};
+
+	use super::*;
+
+	fn pedido(operacion:&str) -> SoapRequest {
+		SoapRequest {
+			url						: "http://127.0.0.1/wsfev1/service.asmx".to_owned(),
+			soap_action		: None,
+			content_type	: "application/soap+xml".to_owned(),
+			body					: format!("<soap:Body><ar:{operacion}/></soap:Body>"),
+			timeout				: None,
+			cuit					: None,
+			webservice		: None,
+		}
+	}
+
+	#[test]
+	fn clasifica_y_demora() {
+		let policy = RetryPolicy { jitter_percent: 0, ..RetryPolicy::default() };
+		assert_eq!(policy.retry_delay(ErrorClass::Transport, 1, false), Some(Duration::from_millis(500)));
+		assert_eq!(policy.retry_delay(ErrorClass::Transport, 2, false), Some(Duration::from_secs(1)));
+		assert_eq!(policy.retry_delay(ErrorClass::Transport, 3, false), None);
+		assert_eq!(policy.retry_delay(ErrorClass::OutcomeUnknown, 1, false), None);
+		assert_eq!(policy.retry_delay(ErrorClass::Rejection, 1, true), None);
+		assert_eq!(ErrType::Soap(SoapFault::new("10016", "numero")).class(), ErrorClass::Rejection);
+		assert_eq!(ErrType::Soap(SoapFault::new("soap:Server", "caido")).class(), ErrorClass::SoapFault);
+	}
+
+	#[cfg(feature = "mock")]
+	#[tokio::test]
+	async fn no_reenvia_facturas_inciertas() {
+		let policy = RetryPolicy { base_delay: Duration::from_millis(1), ..RetryPolicy::default() };
+		let mock = RetryTransport::new(ArcaMock::new(), policy.clone());
+		mock.inner.inject_fault("FEDummy", MockFault::Timeout);
+		assert_eq!(mock.send(pedido("FEDummy")).await.unwrap().status, 200);
+
+		mock.inner.inject_fault("FECAESolicitar", MockFault::Timeout);
+		assert_eq!(mock.send(pedido("FECAESolicitar")).await.unwrap_err().kind, TransportErrorKind::Timeout);
+	}
+
+	#[tokio::test]
+	async fn reenvia_solo_si_no_conecto() {
+		let policy = RetryPolicy { base_delay: Duration::from_millis(1), ..RetryPolicy::default() };
+		let falla_primero = |kind:TransportErrorKind, msg:&'static str| {
+			let intentos = AtomicU32::new(0);
+			RetryTransport::new(MemoryTransport::new(move |_| {
+				match intentos.fetch_add(1, Ordering::SeqCst) {
+					0 => Err(TransportError::new(kind, msg)),
+					_ => Ok(SoapResponse::ok(String::new())),
+				}
+			}), policy.clone())
+		};
+
+		let rechazada = falla_primero(TransportErrorKind::Connect, "connection refused");
+		assert!(rechazada.send(pedido("FECAESolicitar")).await.is_ok());
+		assert_eq!(rechazada.inner.requests().len(), 2);
+
+		let cortada = falla_primero(TransportErrorKind::Other, "connection reset");
+		assert_eq!(cortada.send(pedido("FECAESolicitar")).await.unwrap_err().kind, TransportErrorKind::Other);
+		assert_eq!(cortada.inner.requests().len(), 1);
+	}
+}
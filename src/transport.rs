@@ -0,0 +1,227 @@
+use std::{fmt, future::Future, sync::Mutex, time::Duration};
+
+use crate::types::enums::Webservice;
+
+mod fixtures;
+pub use fixtures::{Fixture, RecordingTransport, ReplayTransport};
+#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
+pub(crate) use fixtures::operation;
+
+///Pedido SOAP listo para enviar. Es lo que arman `auth_arca`, `generar_request` y los `service_status`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoapRequest {
+	pub url						: String,
+	pub soap_action		: Option<String>,
+	pub content_type	: String,
+	pub body					: String,
+	///Si es `None` queda el timeout que tenga configurado el transporte
+	pub timeout				: Option<Duration>,
+	///CUIT en nombre del cual se hace el pedido. `None` en los dummy, que no llevan autenticacion
+	pub cuit					: Option<i64>,
+	///Servicio al que va el pedido. `None` si no se sabe, por ejemplo en lo que recibe `arca-mock`
+	pub webservice		: Option<Webservice>,
+}
+
+///Respuesta HTTP sin interpretar. Un SOAP fault tambien es una respuesta, con su status de error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoapResponse {
+	pub status	: u16,
+	pub headers	: Vec<(String, String)>,
+	pub body		: String,
+}
+
+impl SoapResponse {
+	///Respuesta 200 con el body indicado
+	pub fn ok(body: impl Into<String>) -> Self {
+		SoapResponse { status: 200, headers: vec![("content-type".to_owned(), "text/xml".to_owned())], body: body.into() }
+	}
+}
+
+///Envia pedidos SOAP. Por defecto se usa `reqwest::Client`, y con la feature `blocking` tambien `ureq::Agent`.
+///
+///Implementalo para agregar reintentos, metricas o un proxy propio, o usa `MemoryTransport` para probar sin red
+pub trait SoapTransport: Send + Sync {
+	fn send(&self, req:SoapRequest) -> impl Future<Output = Result<SoapResponse, TransportError>> + Send;
+}
+
+///No se pudo obtener una respuesta HTTP
+#[derive(Debug)]
+pub struct TransportError {
+	pub kind		: TransportErrorKind,
+	pub source	: Box<dyn std::error::Error + Send + Sync>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportErrorKind {
+	///No se pudo conectar con el servidor
+	Connect,
+	Timeout,
+	///El pedido estaba mal armado, por ejemplo una URL invalida
+	Request,
+	Other,
+	///`RateLimitedTransport` no lo envio para no pasarse del limite. El pedido no salio
+	RateLimited,
+}
+
+impl TransportError {
+	pub fn new(kind:TransportErrorKind, source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+		TransportError { kind, source: source.into() }
+	}
+}
+
+impl fmt::Display for TransportError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Error de transporte ({:?}): {}", self.kind, self.source)
+	}
+}
+
+impl std::error::Error for TransportError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(self.source.as_ref())
+	}
+}
+
+
+#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
+impl SoapTransport for reqwest::Client {
+	async fn send(&self, req:SoapRequest) -> Result<SoapResponse, TransportError> {
+		let mut builder = self.post(&req.url)
+			.header(reqwest::header::CONTENT_TYPE, &req.content_type)
+			.body(req.body);
+		if let Some(soap_action) = &req.soap_action {
+			builder = builder.header("SOAPAction", soap_action);
+		}
+		if let Some(timeout) = req.timeout {
+			builder = builder.timeout(timeout);
+		}
+
+		let res = builder.send().await.map_err(reqwest_error)?;
+		let status = res.status().as_u16();
+		let headers = res.headers().iter()
+			.map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+			.collect();
+		let body = res.text().await.map_err(reqwest_error)?;
+		return Ok(SoapResponse { status, headers, body });
+	}
+}
+
+#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
+fn reqwest_error(err:reqwest::Error) -> TransportError {
+	//reqwest marca como `is_request` tambien los timeouts y las conexiones cortadas con el pedido ya enviado
+	let kind = if err.is_timeout() {
+		TransportErrorKind::Timeout
+	} else if err.is_builder() {
+		TransportErrorKind::Request
+	} else if err.is_connect() {
+		TransportErrorKind::Connect
+	} else {
+		TransportErrorKind::Other
+	};
+	return TransportError::new(kind, err);
+}
+
+
+///Bloquea el thread mientras espera la respuesta. Pensado para la API de `crate::blocking`
+#[cfg(feature = "blocking")]
+impl SoapTransport for ureq::Agent {
+	async fn send(&self, req:SoapRequest) -> Result<SoapResponse, TransportError> {
+		let mut builder = self.post(&req.url)
+			.config().timeout_global(req.timeout).http_status_as_error(false).build()
+			.header("Content-Type", &req.content_type);
+		if let Some(soap_action) = &req.soap_action {
+			builder = builder.header("SOAPAction", soap_action);
+		}
+
+		let mut res = builder.send(req.body).map_err(ureq_error)?;
+		let status = res.status().as_u16();
+		let headers = res.headers().iter()
+			.map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+			.collect();
+		let body = res.body_mut().read_to_string().map_err(ureq_error)?;
+		return Ok(SoapResponse { status, headers, body });
+	}
+}
+
+#[cfg(feature = "blocking")]
+fn ureq_error(err:ureq::Error) -> TransportError {
+	let kind = match err {
+		ureq::Error::HostNotFound | ureq::Error::ConnectionFailed => TransportErrorKind::Connect,
+		ureq::Error::Timeout(_) => TransportErrorKind::Timeout,
+		ureq::Error::BadUri(_) | ureq::Error::Http(_) => TransportErrorKind::Request,
+		//Un error de IO (por ejemplo "connection reset") puede llegar con el pedido ya enviado
+		_ => TransportErrorKind::Other,
+	};
+	return TransportError::new(kind, err);
+}
+
+
+type Responder = Box<dyn Fn(&SoapRequest) -> Result<SoapResponse, TransportError> + Send + Sync>;
+
+///Transporte en memoria para tests: cada pedido se guarda y se responde con `responder`, sin tocar la red
+pub struct MemoryTransport {
+	responder	: Responder,
+	requests	: Mutex<Vec<SoapRequest>>,
+}
+
+impl MemoryTransport {
+	pub fn new<F>(responder:F) -> Self
+	where
+		F: Fn(&SoapRequest) -> Result<SoapResponse, TransportError> + Send + Sync + 'static,
+	{
+		MemoryTransport { responder: Box::new(responder), requests: Mutex::new(Vec::new()) }
+	}
+
+	///Los pedidos recibidos hasta ahora, en orden
+	pub fn requests(&self) -> Vec<SoapRequest> {
+		self.requests.lock().unwrap_or_else(|err| err.into_inner()).clone()
+	}
+}
+
+impl SoapTransport for MemoryTransport {
+	async fn send(&self, req:SoapRequest) -> Result<SoapResponse, TransportError> {
+		let res = (self.responder)(&req);
+		self.requests.lock().unwrap_or_else(|err| err.into_inner()).push(req);
+		return res;
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	///Acepta conexiones y nunca responde
+	#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
+	fn servidor_mudo() -> (std::net::TcpListener, String) {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("http://{}/wsfev1/service.asmx", listener.local_addr().unwrap());
+		return (listener, url);
+	}
+
+	#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
+	#[tokio::test]
+	async fn reqwest_timeout_es_timeout() {
+		let (_listener, url) = servidor_mudo();
+		let pedido = SoapRequest {
+			url,
+			soap_action		: None,
+			content_type	: "application/soap+xml".to_owned(),
+			body					: "<soap:Body><ar:FECAESolicitar/></soap:Body>".to_owned(),
+			timeout				: Some(Duration::from_millis(200)),
+			cuit					: None,
+			webservice		: None,
+		};
+		let err = reqwest::Client::new().send(pedido).await.unwrap_err();
+		assert_eq!(err.kind, TransportErrorKind::Timeout);
+		#[cfg(feature = "wsaa")]
+		assert_eq!(crate::types::errors::ErrorClass::of_transport(err.kind), crate::types::errors::ErrorClass::OutcomeUnknown);
+	}
+
+	#[cfg(feature = "blocking")]
+	#[test]
+	fn ureq_solo_conexion_es_connect() {
+		assert_eq!(ureq_error(ureq::Error::ConnectionFailed).kind, TransportErrorKind::Connect);
+		let reset = std::io::Error::from(std::io::ErrorKind::ConnectionReset);
+		assert_eq!(ureq_error(ureq::Error::Io(reset)).kind, TransportErrorKind::Other);
+	}
+}
@@ -0,0 +1,175 @@
+use std::{path::{Path, PathBuf}, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use super::{SoapRequest, SoapResponse, SoapTransport, TransportError, TransportErrorKind};
+
+///Lo que va entre estos tags nunca se guarda en un fixture: el token y el sign de WSAA y el CMS firmado del login
+const REDACTED_TAGS: [&str; 5] = ["Token", "Sign", "token", "sign", "in0"];
+const REDACTED: &str = "REDACTED";
+
+///Un intercambio con ARCA guardado por `RecordingTransport`, con los tokens y firmas tapados
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+	///Metodo SOAP, por ejemplo `loginCms` o `FECAESolicitar`. Es lo que usa `ReplayTransport` para elegir la respuesta
+	pub operation		: String,
+	pub url					: String,
+	pub request			: String,
+	pub status			: u16,
+	pub headers			: Vec<(String, String)>,
+	pub response		: String,
+}
+
+///Envia con `inner` y guarda cada intercambio en `dir` como `NNN_operacion.json`
+pub struct RecordingTransport<T> {
+	inner		: T,
+	dir			: PathBuf,
+	numero	: Mutex<usize>,
+}
+
+impl<T: SoapTransport> RecordingTransport<T> {
+	///Crea el directorio si no existe. Los fixtures nuevos se numeran despues de los que ya haya
+	pub fn new(inner:T, dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+		let dir = dir.into();
+		std::fs::create_dir_all(&dir)?;
+		let numero = std::fs::read_dir(&dir)?.count();
+		Ok(RecordingTransport { inner, dir, numero: Mutex::new(numero) })
+	}
+}
+
+impl<T: SoapTransport> SoapTransport for RecordingTransport<T> {
+	async fn send(&self, req:SoapRequest) -> Result<SoapResponse, TransportError> {
+		let operation = operation(&req);
+		let url = req.url.clone();
+		let request = redact(&req.body);
+		let res = self.inner.send(req).await?;
+
+		let fixture = Fixture { operation, url, request, status: res.status, headers: res.headers.clone(), response: redact(&res.body) };
+		let path = {
+			let mut numero = self.numero.lock().unwrap_or_else(|err| err.into_inner());
+			*numero += 1;
+			self.dir.join(format!("{:03}_{}.json", *numero - 1, fixture.operation))
+		};
+		let json = serde_json::to_vec_pretty(&fixture).map_err(|err| TransportError::new(TransportErrorKind::Other, err))?;
+		std::fs::write(path, json).map_err(|err| TransportError::new(TransportErrorKind::Other, err))?;
+		return Ok(res);
+	}
+}
+
+///Responde con fixtures guardados, sin red. Cada pedido consume el primer fixture sin usar de la misma operacion
+pub struct ReplayTransport {
+	fixtures: Mutex<Vec<Option<Fixture>>>,
+}
+
+impl ReplayTransport {
+	pub fn new(fixtures: Vec<Fixture>) -> Self {
+		ReplayTransport { fixtures: Mutex::new(fixtures.into_iter().map(Some).collect()) }
+	}
+
+	///Carga los `.json` de `dir` en orden de nombre
+	pub fn from_dir(dir: impl AsRef<Path>) -> std::io::Result<Self> {
+		let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+			.map(|entry| entry.map(|entry| entry.path()))
+			.collect::<Result<_, _>>()?;
+		paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+		paths.sort();
+
+		let fixtures = paths.iter()
+			.map(|path| serde_json::from_slice(&std::fs::read(path)?).map_err(std::io::Error::other))
+			.collect::<Result<_, _>>()?;
+		Ok(Self::new(fixtures))
+	}
+}
+
+impl SoapTransport for ReplayTransport {
+	async fn send(&self, req:SoapRequest) -> Result<SoapResponse, TransportError> {
+		let operation = operation(&req);
+		let mut fixtures = self.fixtures.lock().unwrap_or_else(|err| err.into_inner());
+		let fixture = fixtures.iter_mut()
+			.find(|fixture| fixture.as_ref().is_some_and(|fixture| fixture.operation == operation))
+			.and_then(Option::take)
+			.ok_or_else(|| TransportError::new(TransportErrorKind::Other, format!("No hay fixture para {operation}")))?;
+		return Ok(SoapResponse { status: fixture.status, headers: fixture.headers, body: fixture.response });
+	}
+}
+
+
+///El primer elemento dentro del `Body`, sin prefijo. Si el `Body` esta vacio, lo ultimo del SOAPAction
+pub(crate) fn operation(req:&SoapRequest) -> String {
+	let del_body = req.body.split_once("Body>")
+		.and_then(|(_, resto)| resto.trim_start().strip_prefix('<'))
+		.filter(|resto| !resto.starts_with('/'))
+		.map(|resto| resto.split(|c:char| c.is_whitespace() || c == '>' || c == '/').next().unwrap_or_default())
+		.map(|tag| tag.rsplit(':').next().unwrap_or(tag).to_owned());
+	let del_action = || req.soap_action.as_deref()
+		.and_then(|action| action.rsplit('/').next())
+		.filter(|action| !action.is_empty())
+		.map(str::to_owned);
+	return del_body.or_else(del_action).unwrap_or_else(|| "desconocida".to_owned());
+}
+
+///Reemplaza el contenido de los `REDACTED_TAGS`, con o sin prefijo y tambien escapados dentro de otro XML
+fn redact(xml:&str) -> String {
+	let mut xml = xml.to_owned();
+	for tag in REDACTED_TAGS {
+		for (abre, cierra) in [("<", ">"), ("&lt;", "&gt;")] {
+			let mut desde = 0;
+			while let Some(pos) = xml[desde..].find(abre).map(|pos| pos + desde) {
+				let nombre_inicio = pos + abre.len();
+				let Some(fin_tag) = xml[nombre_inicio..].find(cierra).map(|fin| fin + nombre_inicio) else { break };
+				let nombre = &xml[nombre_inicio..fin_tag];
+				desde = fin_tag + cierra.len();
+				if nombre.rsplit(':').next() != Some(tag) {
+					continue;
+				}
+				let cierre = format!("{abre}/{nombre}{cierra}");
+				let Some(fin) = xml[desde..].find(&cierre).map(|fin| fin + desde) else { break };
+				xml.replace_range(desde..fin, REDACTED);
+				desde += REDACTED.len() + cierre.len();
+			}
+		}
+	}
+	return xml;
+}
+
+
+#[cfg(test)]
+mod tests {
+	use crate::transport::MemoryTransport;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn graba_y_reproduce() {
+		let dir = std::env::temp_dir().join(format!("arca_fixtures_{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		let memoria = MemoryTransport::new(|_| Ok(SoapResponse::ok("<credentials><token>PD94</token><sign>c2ln</sign></credentials>".to_owned())));
+		let pedido = SoapRequest {
+			url						: "https://wsaahomo.afip.gov.ar/ws/services/LoginCms".to_owned(),
+			soap_action		: Some(String::new()),
+			content_type	: "text/xml".to_owned(),
+			body					: "<soapenv:Body><wsaa:loginCms><wsaa:in0>MIIG</wsaa:in0></wsaa:loginCms></soapenv:Body>".to_owned(),
+			timeout				: None,
+			cuit					: None,
+			webservice		: None,
+		};
+
+		let grabador = RecordingTransport::new(memoria, &dir).unwrap();
+		grabador.send(pedido.clone()).await.unwrap();
+		let guardado = std::fs::read_to_string(dir.join("000_loginCms.json")).unwrap();
+		assert!(!guardado.contains("PD94") && !guardado.contains("MIIG"));
+
+		let replay = ReplayTransport::from_dir(&dir).unwrap();
+		assert_eq!(replay.send(pedido.clone()).await.unwrap().body, "<credentials><token>REDACTED</token><sign>REDACTED</sign></credentials>");
+		assert_eq!(replay.send(pedido).await.unwrap_err().kind, TransportErrorKind::Other);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn tapa_tokens_y_firmas() {
+		let xml = "<ar:Auth><ar:Token>PD94</ar:Token><ar:Sign>c2ln</ar:Sign><ar:Cuit>20111111112</ar:Cuit></ar:Auth>\
+			<loginCmsReturn>&lt;token&gt;PD94&lt;/token&gt;&lt;sign&gt;c2ln&lt;/sign&gt;</loginCmsReturn><wsaa:in0>MIIG</wsaa:in0>";
+		assert_eq!(redact(xml), "<ar:Auth><ar:Token>REDACTED</ar:Token><ar:Sign>REDACTED</ar:Sign><ar:Cuit>20111111112</ar:Cuit></ar:Auth>\
+			<loginCmsReturn>&lt;token&gt;REDACTED&lt;/token&gt;&lt;sign&gt;REDACTED&lt;/sign&gt;</loginCmsReturn><wsaa:in0>REDACTED</wsaa:in0>");
+	}
+}
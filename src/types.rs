@@ -1,9 +1,9 @@
-#[cfg(feature = "wsaa")]
+#[cfg(any(feature = "wsaa", feature = "dummy_type"))]
 pub mod errors;
-#[cfg(feature = "wsaa")]
-pub(super) mod enums;
+#[cfg(any(feature = "wsaa", feature = "dummy_type", feature = "blocking"))]
+pub mod enums;
 
 #[cfg(feature = "dummy_type")]
-mod dummy_result;
+pub(crate) mod dummy_result;
 #[cfg(feature = "dummy_type")]
 pub use dummy_result::FEDummyResult;
\ No newline at end of file
@@ -1,8 +1,81 @@
+use std::time::{Duration, Instant};
+
+use crate::{transport::{SoapRequest, SoapTransport, TransportErrorKind}, types::enums::Webservice, xml_utils::get_xml_tag};
+
 #[derive(Debug)]
 pub struct FEDummyResult {
-	pub status          : reqwest::StatusCode,
+	///Status HTTP. Si no hubo respuesta: 503 si no se pudo conectar, 408 por timeout, 400 si el pedido era invalido y 500 en otro caso
+	pub status          : u16,
 	pub app_server      : bool,
 	pub db_server       : bool,
 	pub auth_server     : bool,
 	pub milis_respuesta : u128,
-}
\ No newline at end of file
+}
+
+///Como se consulta el metodo dummy de un servicio
+pub(crate) struct DummyRequest<'a> {
+	pub webservice	: Webservice,
+	pub url					: &'a str,
+	pub body				: &'static str,
+	pub soap_action	: Option<&'static str>,
+	///AppServer, DbServer y AuthServer, con las mayusculas que use cada servicio
+	pub tags				: [&'static str; 3],
+}
+
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl FEDummyResult {
+	pub(crate) fn sin_respuesta(status:u16, milis_respuesta:u128) -> Self {
+		return FEDummyResult {
+			status,
+			app_server : false,
+			db_server  : false,
+			auth_server: false,
+			milis_respuesta,
+		};
+	}
+
+	pub(crate) fn from_response(status:u16, milis_respuesta:u128, txt:&str, tags:&[&str; 3]) -> Self {
+		let ok = |tag:&str| get_xml_tag(txt, tag).map(|x| x.to_uppercase().trim() == "OK").unwrap_or(false);
+		return FEDummyResult {
+			status,
+			app_server : ok(tags[0]),
+			db_server  : ok(tags[1]),
+			auth_server: ok(tags[2]),
+			milis_respuesta,
+		};
+	}
+}
+
+pub(crate) async fn consultar<T: SoapTransport>(transport:&T, dummy:&DummyRequest<'_>, timeout:Option<Duration>) -> FEDummyResult {
+	let req = SoapRequest {
+		url						: dummy.url.to_owned(),
+		soap_action		: dummy.soap_action.map(str::to_owned),
+		content_type	: "text/xml".to_owned(),
+		body					: dummy.body.to_owned(),
+		timeout				: Some(timeout.unwrap_or(DEFAULT_TIMEOUT)),
+		cuit					: None,
+		webservice		: Some(dummy.webservice.clone()),
+	};
+
+	let start = Instant::now();
+	let res = transport.send(req).await;
+	let milis = start.elapsed().as_millis();
+
+	match res {
+		Ok(res) => {
+			return FEDummyResult::from_response(res.status, milis, &res.body, &dummy.tags);
+		},
+		Err(er) => {
+			tracing::warn!(url = dummy.url, error = %er, "Fallo la consulta al dummy");
+			let status = match er.kind {
+				TransportErrorKind::Connect => 503,
+				TransportErrorKind::Request => 400,
+				TransportErrorKind::Timeout => 408,
+				TransportErrorKind::Other   => 500,
+				TransportErrorKind::RateLimited => 429,
+			};
+			return FEDummyResult::sin_respuesta(status, milis);
+		},
+	}
+}
@@ -2,20 +2,177 @@ use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+///Servicios de ARCA para los que se puede pedir un ticket a WSAA.
+///`Display` devuelve el nombre del servicio que espera WSAA en el `loginTicketRequest`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum Webservice {
 	Wsaa,
-	Wsfev1
+	///Factura electronica
+	Wsfev1,
+	///Factura electronica de exportacion
+	Wsfexv1,
+	///Factura electronica con detalle de items
+	Wsmtxca,
+	///Carta de porte electronica
+	Wscpe,
+	///Liquidacion primaria de granos
+	Wslpg,
+	///Bonos fiscales electronicos
+	Wsbfev1,
+	///Padron alcance 4
+	WsSrPadronA4,
+	///Padron alcance 5
+	WsSrPadronA5,
+	///Padron alcance 10
+	WsSrPadronA10,
+	///Padron alcance 13
+	WsSrPadronA13,
+	///Constancia de inscripcion
+	WsSrConstanciaInscripcion,
+	///Comprobantes T (turismo)
+	Wsct,
+	///Cualquier otro servicio, con el nombre tal como lo espera WSAA
+	Custom(String),
+}
+
+impl Webservice {
+	///Nombre del servicio en WSAA
+	pub fn service_name(&self) -> &str {
+		match self {
+			Webservice::Wsaa                      => "wsaa",
+			Webservice::Wsfev1                    => "wsfe",
+			Webservice::Wsfexv1                   => "wsfex",
+			Webservice::Wsmtxca                   => "wsmtxca",
+			Webservice::Wscpe                     => "wscpe",
+			Webservice::Wslpg                     => "wslpg",
+			Webservice::Wsbfev1                   => "wsbfe",
+			Webservice::WsSrPadronA4              => "ws_sr_padron_a4",
+			Webservice::WsSrPadronA5              => "ws_sr_padron_a5",
+			Webservice::WsSrPadronA10             => "ws_sr_padron_a10",
+			Webservice::WsSrPadronA13             => "ws_sr_padron_a13",
+			Webservice::WsSrConstanciaInscripcion => "ws_sr_constancia_inscripcion",
+			Webservice::Wsct                      => "wsct",
+			Webservice::Custom(name)              => name,
+		}
+	}
+}
+
+///Reconoce los nombres de servicio de WSAA. Cualquier otro queda como `Custom`
+impl From<&str> for Webservice {
+	fn from(name: &str) -> Self {
+		match name.trim() {
+			"wsaa"                         => Webservice::Wsaa,
+			"wsfe"                         => Webservice::Wsfev1,
+			"wsfex"                        => Webservice::Wsfexv1,
+			"wsmtxca"                      => Webservice::Wsmtxca,
+			"wscpe"                        => Webservice::Wscpe,
+			"wslpg"                        => Webservice::Wslpg,
+			"wsbfe"                        => Webservice::Wsbfev1,
+			"ws_sr_padron_a4"              => Webservice::WsSrPadronA4,
+			"ws_sr_padron_a5"              => Webservice::WsSrPadronA5,
+			"ws_sr_padron_a10"             => Webservice::WsSrPadronA10,
+			"ws_sr_padron_a13"             => Webservice::WsSrPadronA13,
+			"ws_sr_constancia_inscripcion" => Webservice::WsSrConstanciaInscripcion,
+			"wsct"                         => Webservice::Wsct,
+			other                          => Webservice::Custom(other.to_owned()),
+		}
+	}
 }
 
 
 impl fmt::Display for Webservice {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-			let s = match self {
-					Webservice::Wsaa => "wsaa",
-					Webservice::Wsfev1 => "wsfe",
-			};
-			write!(f, "{}", s)
+			write!(f, "{}", self.service_name())
+	}
+}
+
+
+///Ambiente de ARCA contra el que se trabaja. Define la URL de cada servicio y forma parte de la identidad de los tokens guardados
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ArcaEnvironment {
+	Production,
+	Homologation,
+	///URLs propias, por ejemplo un mock local, un proxy de salida o un hostname nuevo de ARCA
+	Custom(CustomEnvironment),
+}
+
+///Ver `ArcaEnvironment::Custom`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct CustomEnvironment {
+	///Identifica al ambiente en el `TokenStore` y en los logs. Tiene que ser distinto de `prod` y `homo`
+	pub name		: String,
+	///Si el ambiente usa los certificados de produccion. Los servicios sin URL propia usan las de ese ambiente
+	pub es_prod	: bool,
+	///URL completa de cada servicio
+	pub urls		: Vec<(Webservice, String)>,
+}
+
+impl CustomEnvironment {
+	pub fn new(name: impl Into<String>, es_prod: bool) -> Self {
+		CustomEnvironment { name: name.into(), es_prod, urls: Vec::new() }
+	}
+
+	///Define la URL de un servicio, reemplazando la anterior si la habia
+	pub fn with_url(mut self, webservice: Webservice, url: impl Into<String>) -> Self {
+		self.urls.retain(|(ws, _)| *ws != webservice);
+		self.urls.push((webservice, url.into()));
+		self
+	}
+}
+
+impl ArcaEnvironment {
+	///Si usa los certificados y tokens de produccion
+	pub fn es_prod(&self) -> bool {
+		match self {
+			ArcaEnvironment::Production   => true,
+			ArcaEnvironment::Homologation => false,
+			ArcaEnvironment::Custom(env)  => env.es_prod,
+		}
+	}
+
+	///`prod`, `homo` o el nombre del ambiente propio
+	pub fn name(&self) -> &str {
+		match self {
+			ArcaEnvironment::Production   => "prod",
+			ArcaEnvironment::Homologation => "homo",
+			ArcaEnvironment::Custom(env)  => &env.name,
+		}
+	}
+
+	///Elige la URL del servicio. `prod` y `homo` son las URLs de ARCA, que usa `Custom` si no tiene una propia
+	#[cfg_attr(not(any(feature = "wsaa", feature = "dummy_type")), allow(dead_code))]
+	pub(crate) fn resolve<'a>(&'a self, webservice: &Webservice, prod: &'a str, homo: &'a str) -> &'a str {
+		match self {
+			ArcaEnvironment::Production   => prod,
+			ArcaEnvironment::Homologation => homo,
+			ArcaEnvironment::Custom(env)  => env.urls.iter()
+				.find(|(ws, _)| ws == webservice)
+				.map(|(_, url)| url.as_str())
+				.unwrap_or(if env.es_prod {prod} else {homo}),
+		}
 	}
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn nombres_wsaa() {
+		assert_eq!(Webservice::Wsfev1.to_string(), "wsfe");
+		assert_eq!(Webservice::from("ws_sr_padron_a13"), Webservice::WsSrPadronA13);
+		assert_eq!(Webservice::from("wsnuevo"), Webservice::Custom("wsnuevo".to_owned()));
+		assert_eq!(Webservice::from("wsnuevo").to_string(), "wsnuevo");
+	}
+
+	#[test]
+	fn urls_por_ambiente() {
+		let mock = ArcaEnvironment::Custom(CustomEnvironment::new("mock", false).with_url(Webservice::Wsaa, "http://localhost:8080/wsaa"));
+		assert_eq!(mock.resolve(&Webservice::Wsaa, "prod", "homo"), "http://localhost:8080/wsaa");
+		assert_eq!(mock.resolve(&Webservice::Wsfev1, "prod", "homo"), "homo");
+		assert_eq!(ArcaEnvironment::Production.resolve(&Webservice::Wsaa, "prod", "homo"), "prod");
+	}
+}
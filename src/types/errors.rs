@@ -1,10 +1,74 @@
-use crate::xml_utils::get_xml_tag;
+use std::{fmt, sync::Arc};
+
+use chrono::{DateTime, Utc};
+
+use crate::{transport::{TransportError, TransportErrorKind}, types::enums::Webservice, xml_utils::get_xml_tag};
 
 #[derive(Debug)]
 pub enum ErrType {
 	Soap(SoapFault),
-	Req(reqwest::Error),
+	///No hubo respuesta HTTP. Ver `SoapTransport`
+	Transport(TransportError),
 	Serde(serde_json::Error),
+	Io(std::io::Error),
+	///Error de una renovacion de token hecha por otra llamada concurrente a `get_token`
+	Shared(Arc<ErrType>),
+	///ARCA no quiso dar un token nuevo porque ya entrego uno vigente (`coe.alreadyAuthenticated`).
+	///`retry_after` es el momento a partir del cual conviene volver a pedirlo
+	AlreadyAuthenticated { retry_after: DateTime<Utc> },
+	///`CircuitBreaker` no envio el pedido porque el servicio viene fallando. Se vuelve a probar a partir de `retry_after`
+	CircuitOpen { webservice: Webservice, retry_after: DateTime<Utc> },
+	///Configuracion invalida, por ejemplo un `WsaaConfig` fuera de los limites de ARCA
+	Config(String),
+	///Fallo un `TraSigner` al firmar el ticket de acceso, por ejemplo un HSM que no responde o un PIN incorrecto
+	Signer(String),
+	///No se pudo firmar el ticket con el certificado y la llave del tenant
+	Crypto(CryptoError),
+}
+
+///Como tratar un error al reintentar. Ver `RetryPolicy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+	///No se pudo conectar: el pedido no llego a ARCA y se puede reenviar
+	Transport,
+	///Timeout o conexion cortada: el pedido pudo haber llegado a ARCA. Solo se reenvia si la operacion es idempotente
+	OutcomeUnknown,
+	///ARCA respondio con un SOAP fault
+	SoapFault,
+	///ARCA proceso el pedido y lo rechazo, por ejemplo un comprobante con la observacion 10016
+	Rejection,
+	///El problema es local: configuracion, certificado, archivos o un pedido mal armado
+	Local,
+}
+
+impl ErrorClass {
+	pub(crate) fn of_transport(kind:TransportErrorKind) -> Self {
+		match kind {
+			TransportErrorKind::Connect => ErrorClass::Transport,
+			TransportErrorKind::Timeout | TransportErrorKind::Other => ErrorClass::OutcomeUnknown,
+			TransportErrorKind::Request | TransportErrorKind::RateLimited => ErrorClass::Local,
+		}
+	}
+}
+
+impl ErrType {
+	///Los rechazos de ARCA llegan como `Soap` con un codigo numerico (10016, 600, etc.). Los SOAP faults traen codigos como `soap:Server`
+	pub fn class(&self) -> ErrorClass {
+		match self {
+			ErrType::Transport(err) => ErrorClass::of_transport(err.kind),
+			ErrType::Soap(fault) if fault.fault_code.as_deref().is_some_and(|code| code.parse::<i64>().is_ok()) => ErrorClass::Rejection,
+			ErrType::Soap(_) | ErrType::AlreadyAuthenticated { .. } => ErrorClass::SoapFault,
+			ErrType::CircuitOpen { .. } => ErrorClass::Transport,
+			ErrType::Shared(err) => err.class(),
+			ErrType::Serde(_) | ErrType::Io(_) | ErrType::Config(_) | ErrType::Signer(_) | ErrType::Crypto(_) => ErrorClass::Local,
+		}
+	}
+}
+
+impl From<CryptoError> for ErrType {
+	fn from(err:CryptoError) -> ErrType {
+		ErrType::Crypto(err)
+	}
 }
 
 impl From<SoapFault> for ErrType {
@@ -13,9 +77,9 @@ impl From<SoapFault> for ErrType {
 	}
 }
 
-impl From<reqwest::Error> for ErrType {
-	fn from(err:reqwest::Error) -> ErrType {
-		ErrType::Req(err)
+impl From<TransportError> for ErrType {
+	fn from(err:TransportError) -> ErrType {
+		ErrType::Transport(err)
 	}
 }
 
@@ -25,6 +89,12 @@ impl From<serde_json::Error> for ErrType {
 	}
 }
 
+impl From<std::io::Error> for ErrType {
+	fn from(err:std::io::Error) -> ErrType {
+		ErrType::Io(err)
+	}
+}
+
 #[derive(Debug)]
 pub struct SoapFault {
 	pub fault_code  : Option<String>,
@@ -41,4 +111,41 @@ impl SoapFault {
 			fault_code: get_xml_tag(xml,"faultcode")
 		}
 	}
-}
\ No newline at end of file
+}
+
+///Problemas con el certificado o la llave privada al firmar el ticket de acceso.
+///Cada variante incluye el detalle de OpenSSL, pensado para mostrarselo al tenant que subio los archivos
+#[derive(Debug)]
+pub enum CryptoError {
+	///El certificado no es un X509 valido
+	InvalidCert(String),
+	///La llave privada no se pudo leer
+	InvalidKey(String),
+	///La llave privada esta encriptada y no se indico la passphrase
+	PassphraseRequired,
+	///La passphrase no abre la llave privada
+	WrongPassphrase,
+	///El archivo PKCS#12 / PFX no se pudo abrir, no tiene certificado y llave, o la contraseña es incorrecta
+	InvalidPkcs12(String),
+	///La llave privada no corresponde al certificado
+	KeyMismatch,
+	///OpenSSL no pudo armar o serializar el CMS firmado
+	Signing(String),
+	///No se pudo generar la llave o el CSR, o no cumplen lo que exige ARCA
+	Csr(String),
+}
+
+impl fmt::Display for CryptoError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CryptoError::InvalidCert(det) => write!(f, "No se pudo leer el certificado como X509: {det}"),
+			CryptoError::InvalidKey(det)  => write!(f, "No se pudo leer la llave privada: {det}"),
+			CryptoError::PassphraseRequired => write!(f, "La llave privada esta encriptada y no se indico la contraseña"),
+			CryptoError::WrongPassphrase  => write!(f, "La contraseña de la llave privada es incorrecta"),
+			CryptoError::InvalidPkcs12(det) => write!(f, "No se pudo abrir el archivo PKCS#12 / PFX: {det}"),
+			CryptoError::KeyMismatch      => write!(f, "La llave privada no coincide con el certificado"),
+			CryptoError::Signing(det)     => write!(f, "No se pudo firmar el ticket de acceso: {det}"),
+			CryptoError::Csr(det)         => write!(f, "No se pudo generar el pedido de certificado: {det}"),
+		}
+	}
+}
@@ -1,4 +1,11 @@
 mod auth_arca;
+#[cfg(feature = "blocking")]
+pub(crate) mod blocking;
+pub mod config;
+pub mod generate_csr;
 pub mod get_token;
+pub mod refresher;
+pub mod sso;
+pub mod token_store;
 mod url;
-pub mod validate_crt;
\ No newline at end of file
+pub mod validate_crt;
@@ -1,64 +1,86 @@
-use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, Utc};
-use reqwest::{header::CONTENT_TYPE, Client};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
 
-use crate::{crypto::sign_cms::sign_cms, types::{enums::Webservice, errors::{ErrType, SoapFault}}, wsaa::url::{URL_HOMO, URL_PROD}, xml_utils::get_xml_tag};
+use crate::{crypto::signer::TraSigner, transport::{SoapRequest, SoapTransport}, types::{enums::{ArcaEnvironment, Webservice}, errors::{ErrType, SoapFault}}, wsaa::url::{URL_HOMO, URL_PROD}, xml_utils::get_xml_tag};
 
-use super::get_token::TokenArca;
+use super::{config::WsaaConfig, get_token::TokenArca};
 
-pub async fn auth_arca(
-	webservice		: Webservice,
-	cert_contents	: &Vec<u8>,
-	key_contents	: &Vec<u8>,
-	req_cli				: &Client,
-	es_prod				: bool,
-	cuit					: i64,
+const LOGIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+pub(crate) async fn auth_arca<C: TraSigner, T: SoapTransport>(
+	webservice		: &Webservice,
+	signer				: &C,
+	transport			: &T,
+	environment		: &ArcaEnvironment,
+	config				: &WsaaConfig,
 ) -> Result<TokenArca, ErrType> {
-	let url = if es_prod {URL_PROD} else {URL_HOMO};
+	let request_xml = login_cms_request(webservice, signer, config)?;
+
+	let response = transport.send(SoapRequest {
+		url						: login_url(environment).to_owned(),
+		soap_action		: Some(String::new()),
+		content_type	: "text/xml".to_owned(),
+		body					: request_xml,
+		timeout				: Some(LOGIN_TIMEOUT),
+		cuit					: Some(signer.cuit()),
+		webservice		: Some(Webservice::Wsaa),
+	}).await?;
+
+	return login_cms_response(signer.cuit(), &response.body);
+}
+
+
+fn login_url(environment:&ArcaEnvironment) -> &str {
+	return environment.resolve(&Webservice::Wsaa, URL_PROD, URL_HOMO);
+}
 
-	let tz:FixedOffset = FixedOffset::west_opt(3600*3).unwrap();
-	let req_date = Utc::now() - Duration::minutes(5);
-	let exp_date = req_date + Duration::hours(23);
+///Arma y firma el sobre SOAP de `loginCms`
+fn login_cms_request<C: TraSigner>(webservice:&Webservice, signer:&C, config:&WsaaConfig) -> Result<String, ErrType> {
+	let req_date = Utc::now() - config.backdate();
+	let exp_date = req_date + config.ta_lifetime();
 	let login_ticket = login_ticket_request_xml(webservice, req_date, exp_date);
 	
-	let signed_ticket = sign_cms(
-		cert_contents,
-		key_contents,
-		login_ticket.as_str());
-	let request_xml = make_xml(&signed_ticket);
-
-	let response = req_cli.post(url)
-		.header(CONTENT_TYPE, "text/xml")
-		.header("SOAPAction", "")
-		.body(request_xml)
-		.timeout(std::time::Duration::from_secs(60))
-		.send().await?
-		.text().await?;
+	let signed_ticket = signer.sign_tra(login_ticket.as_str())?;
 
+	return Ok(make_xml(&signed_ticket));
+}
+
+///Interpreta la respuesta de `loginCms`, sea un fault o el ticket de acceso
+fn login_cms_response(cuit:i64, response:&str) -> Result<TokenArca, ErrType> {
 	if response.contains("<faultcode") {
 		if response.contains("ns1:coe.alreadyAuthenticated") {
-			return Err(SoapFault::new(
-				"alreadyAuthenticated",
-				"Estas renovando el login muy rapido y ARCA no quiso darte uno nuevo. Intenta en unos minutos"
-			).into());
+			//ARCA no informa hasta cuando vale el token anterior. Se sugieren los 10 minutos que suele tardar en liberarlo
+			return Err(ErrType::AlreadyAuthenticated { retry_after: Utc::now() + Duration::minutes(10) });
 		} else {
-			dbg!(&response);
-			return Err(SoapFault::from_xml(&response).into());
+			tracing::warn!(response, "WSAA respondio con un fault");
+			return Err(SoapFault::from_xml(response).into());
 		}
 	};
-	
 
-	let expir_str = get_xml_tag(&response, "expirationTime")
-		.ok_or(SoapFault::new("parseError","No se encontro expirationTime en la respuesta de afip"))?;
-	let token= get_xml_tag(&response, "token")
-		.ok_or(SoapFault::new("parseError","No se encontro token en la respuesta de afip"))?;
-	let sign = get_xml_tag(&response, "sign")
-		.ok_or(SoapFault::new("parseError","No se encontro sign en la respuesta de afip"))?;
-	let expir = NaiveDateTime::parse_from_str(&expir_str,"%Y-%m-%dT%H:%M:%S%.f%:z").unwrap();
-	//dbg!(&expir);
-	let expir = expir.and_local_timezone(tz).unwrap().to_utc();
-	//dbg!(&expir);
-	
-	return Ok(TokenArca{cuit, token, sign, expir});
+	return login_ticket_response(cuit, response);
+}
+
+///Arma el `TokenArca` con el `loginTicketResponse` que viene dentro de la respuesta de `loginCms`
+fn login_ticket_response(cuit:i64, response:&str) -> Result<TokenArca, ErrType> {
+	let tag = |tag:&str| get_xml_tag(response, tag)
+		.ok_or(SoapFault::new("parseError", &format!("No se encontro {tag} en la respuesta de afip")));
+	let fecha = |tag_fecha:&str| -> Result<DateTime<FixedOffset>, SoapFault> {
+		let fecha = tag(tag_fecha)?;
+		return DateTime::parse_from_rfc3339(fecha.trim())
+			.map_err(|err| SoapFault::new("parseError", &format!("{tag_fecha} invalido en la respuesta de afip ({fecha}): {err}")));
+	};
+
+	let unique_id = tag("uniqueId")?;
+	return Ok(TokenArca {
+		cuit,
+		token						: tag("token")?,
+		sign						: tag("sign")?,
+		source					: tag("source")?,
+		destination			: tag("destination")?,
+		unique_id				: unique_id.trim().parse()
+			.map_err(|_| SoapFault::new("parseError", &format!("uniqueId invalido en la respuesta de afip ({unique_id})")))?,
+		generation_time	: fecha("generationTime")?,
+		expiration_time	: fecha("expirationTime")?,
+	});
 }
 
 
@@ -77,7 +99,7 @@ fn make_xml(signed_request:&str) -> String {
 
 
 fn login_ticket_request_xml(
-	webservice:Webservice,req_date: DateTime<Utc>,exp_date: DateTime<Utc>
+	webservice:&Webservice,req_date: DateTime<Utc>,exp_date: DateTime<Utc>
 ) -> String {
 
 	let webservice = webservice.to_string();
@@ -101,3 +123,39 @@ fn login_ticket_request_xml(
 
 
 
+
+
+#[cfg(test)]
+pub(crate) fn login_response_de_prueba(generado:&str, expira:&str) -> String {
+	return format!(r#"<soapenv:Body><loginCmsResponse><loginCmsReturn>&lt;?xml version="1.0" encoding="UTF-8" standalone="yes"?&gt;
+&lt;loginTicketResponse version="1.0"&gt;
+    &lt;header&gt;
+        &lt;source&gt;CN=wsaahomo, O=AFIP, C=AR, SERIALNUMBER=CUIT 33693450239&lt;/source&gt;
+        &lt;destination&gt;SERIALNUMBER=CUIT 20111111112, CN=facturador&lt;/destination&gt;
+        &lt;uniqueId&gt;1234567890&lt;/uniqueId&gt;
+        &lt;generationTime&gt;{generado}&lt;/generationTime&gt;
+        &lt;expirationTime&gt;{expira}&lt;/expirationTime&gt;
+    &lt;/header&gt;
+    &lt;credentials&gt;
+        &lt;token&gt;PD94bWwg&lt;/token&gt;
+        &lt;sign&gt;c2lnbg==&lt;/sign&gt;
+    &lt;/credentials&gt;
+&lt;/loginTicketResponse&gt;</loginCmsReturn></loginCmsResponse></soapenv:Body>"#);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parsea_login_ticket_response() {
+		let response = login_response_de_prueba("2026-10-18T10:00:00.123-03:00", "2026-10-18T22:00:00.123-03:00");
+
+		let ta = login_ticket_response(20111111112, &response).unwrap();
+		assert_eq!(ta.destination(), "SERIALNUMBER=CUIT 20111111112, CN=facturador");
+		assert_eq!(ta.unique_id(), 1234567890);
+		assert_eq!(ta.expiration_time().offset().local_minus_utc(), -3 * 3600);
+		assert_eq!(ta.expires_at().to_rfc3339(), "2026-10-19T01:00:00.123+00:00");
+		assert_eq!((ta.token(), ta.sign()), ("PD94bWwg", "c2lnbg=="));
+	}
+}
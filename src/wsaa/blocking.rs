@@ -0,0 +1,36 @@
+use crate::{blocking::block_on, crypto::signer::TraSigner, transport::SoapTransport, types::errors::{ErrType, SoapFault}};
+
+use super::{auth_arca::auth_arca, config::WsaaConfig, get_token::{renovar, una_renovacion, ServiceId}, token_store::TokenStore};
+
+
+///Igual que `get_token`, pero sincronica. No necesita un runtime de tokio.
+///
+///El `token_store` se usa igual que en la version async, asi que ambas comparten los tokens guardados y las renovaciones en curso.
+///Sus futures y los de `transport` se ejecutan en el thread actual, por lo que no pueden depender de un runtime
+///(los de `DashMap`, `FileTokenStore`, `ureq::Agent` y `MemoryTransport` no lo hacen)
+pub fn get_token<S, T, Fc, C, Fp>(
+	token_store			: &S,
+	key							: ServiceId,
+	transport				: &T,
+	config					: &WsaaConfig,
+	mut cert_key_getter	: Fc,
+	token_parser  	: Fp,
+)-> Result<String, ErrType> 
+where 
+	S: TokenStore,
+	T: SoapTransport,
+	Fc: FnMut(ServiceId) -> Option<C>,
+	C: TraSigner,
+	Fp: FnOnce(&ServiceId, i64, &str, &str) -> String,
+{
+	config.validate()?;
+	let value = block_on(una_renovacion(token_store, key.clone(), config.refresh_margin(), async |anterior| {
+		let signer = cert_key_getter(key.clone()).ok_or(SoapFault::new("db", "No se encontro el par de Certificado y Key"))?;
+		return renovar(&key, anterior, config,
+			async || auth_arca(&key.webservice, &signer, transport, &key.environment, config).await,
+			async |espera| std::thread::sleep(espera),
+		).await;
+	}))?;
+
+	return Ok(token_parser(&key, value.cuit, &value.token, &value.sign));
+}
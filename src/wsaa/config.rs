@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use chrono::TimeDelta;
+
+use crate::types::errors::ErrType;
+
+
+///ARCA rechaza los pedidos de ticket (TRA) con una vigencia mayor a 24 horas
+pub const MAX_TRA_LIFETIME: Duration = Duration::from_secs(24 * 3600);
+///ARCA nunca entrega tickets (TA) que duren mas de 12 horas, sin importar lo que se pida
+pub const MAX_TA_VALIDITY: Duration = Duration::from_secs(12 * 3600);
+
+
+///Configuracion del login contra WSAA que usa `get_token`
+#[derive(Debug, Clone)]
+pub struct WsaaConfig {
+	///Vigencia pedida para el ticket, contada desde `generationTime`. ARCA puede entregar uno mas corto
+	pub ta_lifetime		: Duration,
+	///Cuanto antes del vencimiento se considera que el token hay que renovarlo
+	pub refresh_margin: Duration,
+	///Cuanto se atrasa `generationTime` respecto de la hora actual, para tolerar relojes desfasados con ARCA
+	pub backdate			: Duration,
+	///Que hacer si ARCA responde `coe.alreadyAuthenticated` al renovar el token
+	pub already_auth	: AlreadyAuthPolicy,
+}
+
+impl Default for WsaaConfig {
+	fn default() -> Self {
+		Self {
+			ta_lifetime		: Duration::from_secs(23 * 3600),
+			refresh_margin: Duration::from_secs(15 * 60),
+			backdate			: Duration::from_secs(5 * 60),
+			already_auth	: AlreadyAuthPolicy::default(),
+		}
+	}
+}
+
+impl WsaaConfig {
+	///Verifica que la configuracion respete los limites de ARCA. `get_token` la valida antes de usarla
+	pub fn validate(&self) -> Result<(), ErrType> {
+		if self.ta_lifetime > MAX_TRA_LIFETIME {
+			return Err(ErrType::Config("ta_lifetime no puede superar las 24 horas".to_owned()));
+		}
+		if self.ta_lifetime <= self.backdate {
+			return Err(ErrType::Config("ta_lifetime debe ser mayor que backdate, o el ticket nace vencido".to_owned()));
+		}
+		let vigencia = (self.ta_lifetime - self.backdate).min(MAX_TA_VALIDITY);
+		if self.refresh_margin >= vigencia {
+			return Err(ErrType::Config(format!("refresh_margin debe ser menor a la vigencia real del ticket ({} minutos)", vigencia.as_secs() / 60)));
+		}
+		if let AlreadyAuthPolicy::Retry { interval, .. } = self.already_auth && interval.is_zero() {
+			return Err(ErrType::Config("El interval de AlreadyAuthPolicy::Retry no puede ser cero".to_owned()));
+		}
+		return Ok(());
+	}
+
+	pub(super) fn ta_lifetime(&self) -> TimeDelta {
+		to_delta(self.ta_lifetime)
+	}
+
+	pub(super) fn refresh_margin(&self) -> TimeDelta {
+		to_delta(self.refresh_margin)
+	}
+
+	pub(super) fn backdate(&self) -> TimeDelta {
+		to_delta(self.backdate)
+	}
+}
+
+///Las duraciones validadas nunca superan las 24 horas, asi que la conversion no falla
+fn to_delta(dur:Duration) -> TimeDelta {
+	TimeDelta::from_std(dur).unwrap_or(TimeDelta::MAX)
+}
+
+
+///Que hacer cuando ARCA se niega a dar un token nuevo porque ya entrego uno que sigue vigente (`coe.alreadyAuthenticated`).
+///
+///Pasa cuando se pierde el token (un deploy, un crash) o cuando otro proceso lo renovo con el mismo certificado.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlreadyAuthPolicy {
+	///Devuelve `ErrType::AlreadyAuthenticated` con el momento a partir del cual conviene reintentar
+	#[default]
+	Fail,
+	///Usa el token que ya estaba guardado para ese `ServiceId` mientras no haya vencido.
+	///Si no hay ninguno vigente se comporta como `Fail`
+	UsePersisted,
+	///Reintenta el login cada `interval` hasta que pase `max_wait`. Si se agota el tiempo se comporta como `Fail`
+	Retry {
+		interval: Duration,
+		max_wait: Duration,
+	},
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn limites() {
+		assert!(WsaaConfig::default().validate().is_ok());
+
+		let largo = WsaaConfig { ta_lifetime: Duration::from_secs(25 * 3600), ..Default::default() };
+		assert!(matches!(largo.validate(), Err(ErrType::Config(_))));
+
+		let margen = WsaaConfig { refresh_margin: Duration::from_secs(13 * 3600), ..Default::default() };
+		assert!(matches!(margen.validate(), Err(ErrType::Config(_))));
+
+		let corto = WsaaConfig { ta_lifetime: Duration::from_secs(3600), refresh_margin: Duration::from_secs(10 * 60), ..Default::default() };
+		assert!(corto.validate().is_ok());
+	}
+}
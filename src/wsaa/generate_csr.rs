@@ -0,0 +1,117 @@
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::symm::Cipher;
+use openssl::x509::{X509Name, X509Req};
+
+use crate::{crypto::load::parse_key, types::errors::CryptoError};
+
+
+/// ARCA no acepta llaves RSA de menos de 2048 bits
+pub const MIN_KEY_BITS: u32 = 2048;
+
+/// Datos del solicitante que ARCA exige en el subject del CSR
+#[derive(Debug, Clone)]
+pub struct CsrSubject {
+    /// Razon social. Va como `O`
+    pub company: String,
+    /// Nombre del certificado dentro de ARCA. Va como `CN`
+    pub alias: String,
+    /// Va como `serialNumber=CUIT <cuit>`
+    pub cuit: i64,
+}
+
+#[derive(Debug)]
+pub struct GeneratedCsr {
+    /// Llave privada en PEM PKCS#8. Encriptada si se indico una passphrase
+    pub key_pem: Vec<u8>,
+    /// CSR en PEM, listo para subir a ARCA
+    pub csr_pem: Vec<u8>,
+}
+
+/// Genera una llave RSA nueva y el CSR para pedir el certificado a ARCA.
+///
+/// Cuando ARCA devuelve el certificado, `key_pem` y el certificado se pueden pasar tal cual a `inspect_cert` y a `CertKeyPair`
+pub fn generate_key_and_csr(subject: &CsrSubject, key_bits: u32, key_passphrase: Option<&str>) -> Result<GeneratedCsr, CryptoError> {
+    if key_bits < MIN_KEY_BITS {
+        return Err(CryptoError::Csr(format!("La llave debe tener al menos {MIN_KEY_BITS} bits")));
+    }
+
+    let key = Rsa::generate(key_bits)
+        .and_then(PKey::from_rsa)
+        .map_err(|err| CryptoError::Csr(err.to_string()))?;
+
+    let key_pem = match key_passphrase {
+        Some(pass) => key.private_key_to_pem_pkcs8_passphrase(Cipher::aes_256_cbc(), pass.as_bytes()),
+        None => key.private_key_to_pem_pkcs8(),
+    }.map_err(|err| CryptoError::Csr(err.to_string()))?;
+
+    let csr_pem = make_csr(subject, &key)?;
+    Ok(GeneratedCsr { key_pem, csr_pem })
+}
+
+/// Genera el CSR para renovar un certificado, reutilizando la llave privada existente.
+/// La llave puede venir en cualquiera de los formatos que acepta `CertKeyPair`
+pub fn renewal_csr(subject: &CsrSubject, key_contents: &[u8], key_passphrase: Option<&str>) -> Result<Vec<u8>, CryptoError> {
+    let key = parse_key(key_contents, key_passphrase)?;
+    if key.bits() < MIN_KEY_BITS {
+        return Err(CryptoError::Csr(format!("La llave tiene {} bits. ARCA exige al menos {MIN_KEY_BITS}; genere una nueva", key.bits())));
+    }
+    make_csr(subject, &key)
+}
+
+fn make_csr(subject: &CsrSubject, key: &PKey<Private>) -> Result<Vec<u8>, CryptoError> {
+    let csr_err = |err: openssl::error::ErrorStack| CryptoError::Csr(err.to_string());
+
+    if subject.company.trim().is_empty() || subject.alias.trim().is_empty() {
+        return Err(CryptoError::Csr("La razon social y el alias no pueden estar vacios".to_owned()));
+    }
+
+    let mut name = X509Name::builder().map_err(csr_err)?;
+    name.append_entry_by_nid(Nid::COUNTRYNAME, "AR").map_err(csr_err)?;
+    name.append_entry_by_nid(Nid::ORGANIZATIONNAME, subject.company.trim()).map_err(csr_err)?;
+    name.append_entry_by_nid(Nid::COMMONNAME, subject.alias.trim()).map_err(csr_err)?;
+    name.append_entry_by_nid(Nid::SERIALNUMBER, &format!("CUIT {}", subject.cuit)).map_err(csr_err)?;
+    let name = name.build();
+
+    let mut req = X509Req::builder().map_err(csr_err)?;
+    req.set_version(0).map_err(csr_err)?;
+    req.set_subject_name(&name).map_err(csr_err)?;
+    req.set_pubkey(key).map_err(csr_err)?;
+    req.sign(key, MessageDigest::sha256()).map_err(csr_err)?;
+
+    req.build().to_pem().map_err(csr_err)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subject() -> CsrSubject {
+        CsrSubject { company: "Empresa SA".to_owned(), alias: "facturador".to_owned(), cuit: 20111111112 }
+    }
+
+    #[test]
+    fn csr_con_subject_de_arca() {
+        let generado = generate_key_and_csr(&subject(), 2048, Some("secreto")).unwrap();
+        let req = X509Req::from_pem(&generado.csr_pem).unwrap();
+        let key = parse_key(&generado.key_pem, Some("secreto")).unwrap();
+        assert!(req.verify(&key).unwrap());
+
+        let entry = |nid| String::from_utf8_lossy(req.subject_name().entries_by_nid(nid).next().unwrap().data().as_slice()).into_owned();
+        assert_eq!(entry(Nid::COUNTRYNAME), "AR");
+        assert_eq!(entry(Nid::ORGANIZATIONNAME), "Empresa SA");
+        assert_eq!(entry(Nid::COMMONNAME), "facturador");
+        assert_eq!(entry(Nid::SERIALNUMBER), "CUIT 20111111112");
+
+        let renovacion = X509Req::from_pem(&renewal_csr(&subject(), &generado.key_pem, Some("secreto")).unwrap()).unwrap();
+        assert!(renovacion.public_key().unwrap().public_eq(&req.public_key().unwrap()));
+    }
+
+    #[test]
+    fn rechaza_llaves_chicas() {
+        assert!(matches!(generate_key_and_csr(&subject(), 1024, None), Err(CryptoError::Csr(_))));
+    }
+}
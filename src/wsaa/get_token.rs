@@ -1,59 +1,423 @@
-use std::sync::Arc;
+use std::{sync::{Arc, LazyLock}, time::Instant};
 
-use chrono::{DateTime, Duration, Utc};
-use reqwest::Client;
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use dashmap::{mapref::entry::Entry, DashMap};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
 
-use crate::{types::{enums::Webservice, errors::{ErrType, SoapFault}}, wsaa::auth_arca::auth_arca};
+use crate::{crypto::{load::pkcs12_to_pem, signer::TraSigner}, transport::SoapTransport, types::{enums::{ArcaEnvironment, Webservice}, errors::{CryptoError, ErrType, SoapFault}}, wsaa::{auth_arca::auth_arca, config::{AlreadyAuthPolicy, WsaaConfig}, token_store::TokenStore}};
 
 
+type Renovacion = Result<TokenArca, Arc<ErrType>>;
 
-///Busca el token de dentro de un DashMap. Si no lo encuentra o si esta expirado, lo renueva.
+///`TokenStore::store_id` del store y el token que se renueva
+type Clave = (usize, ServiceId);
+
+///Renovaciones de token en curso. Las llamadas concurrentes para el mismo store y `ServiceId` esperan el resultado de la primera
+static EN_CURSO: LazyLock<DashMap<Clave, watch::Sender<Option<Renovacion>>>> = LazyLock::new(DashMap::new);
+
+
+///Busca el token dentro del `token_store`. Si no lo encuentra o si esta expirado, lo renueva y lo guarda.
+/// 
+/// Si varias llamadas con el mismo `token_store` necesitan renovar el mismo `ServiceId` al mismo tiempo, solo una hace el login
+/// contra WSAA y el resto recibe su resultado. Con stores distintos cada uno hace su login, asi que conviene usar uno solo por proceso.
+/// 
+/// `transport` envia el pedido a WSAA: un `reqwest::Client` o cualquier otro `SoapTransport`.
 /// 
-/// `cert_key_getter` solo se llama en caso de que sea necesario renovar el token. 
-/// Debe devoler el contenido del certificado y la llave privada, ya sea leyendolo de un archivo o de la base de datos.
+/// `config` define la vigencia pedida, con cuanta anticipacion se renueva y que hacer si ARCA responde `coe.alreadyAuthenticated`.
+/// Se valida antes de usarla. Ver `WsaaConfig`
 /// 
-/// `token_parser` Recibe el token y el sign y deberia devolver un string formateado. Es el resultado final de la funcion
-pub async fn get_token<'a, Fc>(
-	token_map				: Arc<dashmap::DashMap<ServiceId, TokenArca>>,
+/// El ambiente lo define `key` (ver `ArcaEnvironment`), y forma parte de la identidad del token guardado.
+/// 
+/// `cert_key_getter` solo se llama en caso de que sea necesario renovar el token, y una sola vez por renovacion. 
+/// Recibe `key` y debe devolver con que firmar el ticket en ese ambiente: un `CertKeyPair` con el certificado y la llave privada,
+/// ya sea leyendolos de un archivo o de la base de datos, o cualquier otro `TraSigner` (por ejemplo un HSM).
+/// 
+/// `token_parser` Recibe `key`, el cuit del certificado, el token y el sign y deberia devolver un string formateado. Es el resultado final de la funcion
+pub async fn get_token<S, T, Fc, C, Fp>(
+	token_store			: &S,
 	key							: ServiceId,
-	es_prod					: bool,
-	req_cli					: &Client,
+	transport				: &T,
+	config					: &WsaaConfig,
 	mut cert_key_getter	: Fc,
-	token_parser  	: fn(i64, &str, &str) -> String
+	token_parser  	: Fp,
 )-> Result<String, ErrType> 
 where 
-	Fc: AsyncFnMut() -> Option<CertKeyPair>,
+	S: TokenStore,
+	T: SoapTransport,
+	Fc: AsyncFnMut(ServiceId) -> Option<C>,
+	C: TraSigner,
+	Fp: FnOnce(&ServiceId, i64, &str, &str) -> String,
 {
-	if let Some(rf) = token_map.get(&key) {
-		let current_time = dbg!(Utc::now()+Duration::minutes(15));
-		if rf.value().expir > current_time {
-			return Ok(token_parser(rf.cuit, &rf.token, &rf.sign));
+	config.validate()?;
+	let value = una_renovacion(token_store, key.clone(), config.refresh_margin(), async |anterior| {
+		let signer = cert_key_getter(key.clone()).await.ok_or(SoapFault::new("db", "No se encontro el par de Certificado y Key"))?;
+		return renovar(&key, anterior, config,
+			async || auth_arca(&key.webservice, &signer, transport, &key.environment, config).await,
+			async |espera| tokio::time::sleep(espera).await,
+		).await;
+	}).await?;
+
+	return Ok(token_parser(&key, value.cuit, &value.token, &value.sign));
+}
+
+
+///Hace el login aplicando `config.already_auth` si ARCA responde `coe.alreadyAuthenticated`.
+///`login` y `dormir` permiten usarla tanto desde la version async como desde la sincronica
+pub(crate) async fn renovar(
+	key					: &ServiceId,
+	anterior		: Option<TokenArca>,
+	config			: &WsaaConfig,
+	mut login		: impl AsyncFnMut() -> Result<TokenArca, ErrType>,
+	dormir			: impl AsyncFn(std::time::Duration),
+) -> Result<TokenArca, ErrType> {
+	let inicio = Instant::now();
+	loop {
+		let retry_after = match login().await {
+			Err(ErrType::AlreadyAuthenticated { retry_after }) => retry_after,
+			res => return res,
+		};
+
+		match config.already_auth {
+			AlreadyAuthPolicy::Fail => {},
+			AlreadyAuthPolicy::UsePersisted => {
+				if let Some(vigente) = anterior.as_ref().filter(|tok| tok.expires_at() > Utc::now()) {
+					tracing::warn!(?key, "ARCA respondio alreadyAuthenticated. Se usa el token guardado hasta que venza");
+					return Ok(vigente.clone());
+				}
+			},
+			AlreadyAuthPolicy::Retry { interval, max_wait } => {
+				if inicio.elapsed() + interval <= max_wait {
+					dormir(interval).await;
+					continue;
+				}
+			},
 		}
-	};
 
-	let CertKeyPair {cuit, cert_contents, key_contents} = cert_key_getter().await.ok_or(SoapFault::new("db", "No se encontro el par de Certificado y Key"))?;
-	let value = auth_arca(key.webservice, &cert_contents, &key_contents, req_cli, es_prod, cuit).await?;
-	let retorno = token_parser(cuit, &value.token, &value.sign);
-	token_map.insert(key, value);
-	return Ok(retorno);
+		//Si conocemos el token anterior, ARCA no va a dar otro hasta que ese venza
+		let retry_after = anterior.as_ref().map(|tok| tok.expires_at()).filter(|expir| *expir > Utc::now()).unwrap_or(retry_after);
+		return Err(ErrType::AlreadyAuthenticated { retry_after });
+	}
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+///Devuelve el token vigente del store o, si no hay, ejecuta `renovar` y lo guarda.
+///Si ya hay una renovacion en curso para `key` en el mismo store, espera su resultado en vez de ejecutar `renovar`.
+///`renovar` recibe el token guardado anteriormente, si lo habia
+pub(crate) async fn una_renovacion<S, Fr>(
+	token_store	: &S,
+	key					: ServiceId,
+	margen			: Duration,
+	renovar			: Fr,
+) -> Result<TokenArca, ErrType>
+where 
+	S: TokenStore,
+	Fr: AsyncFnOnce(Option<TokenArca>) -> Result<TokenArca, ErrType>,
+{
+	let vigente = |token:&TokenArca| token.expires_at() > Utc::now()+margen;
+	let clave = (token_store.store_id(), key.clone());
+	loop {
+		if let Some(rf) = token_store.get(&key).await? && vigente(&rf) {
+			return Ok(rf);
+		}
+
+		let mut rx = match EN_CURSO.entry(clave.clone()) {
+			Entry::Occupied(en_curso) => en_curso.get().subscribe(),
+			Entry::Vacant(libre) => {
+				libre.insert(watch::channel(None).0);
+				break;
+			},
+		};
+
+		match rx.wait_for(|res| res.is_some()).await {
+			Ok(res) => return match res.as_ref() {
+				Some(Ok(value)) => Ok(value.clone()),
+				Some(Err(err))  => Err(ErrType::Shared(err.clone())),
+				None            => unreachable!(),
+			},
+			//La renovacion en curso se cancelo antes de terminar. Se vuelve a intentar
+			Err(_) => continue,
+		}
+	}
+
+	let _guard = FinRenovacion(clave.clone());
+	//Otra renovacion pudo terminar entre la lectura del store y el insert en EN_CURSO
+	let res = match token_store.get(&key).await {
+		Ok(Some(rf)) if vigente(&rf) => Ok(rf),
+		Ok(anterior) => match renovar(anterior).await {
+			Ok(value) => token_store.put(&key, &value).await.map(|_| value),
+			Err(err)  => Err(err),
+		},
+		Err(err) => Err(err),
+	}.map_err(Arc::new);
+
+	if let Some(tx) = EN_CURSO.get(&clave) {
+		tx.send_replace(Some(res.clone()));
+	}
+	drop(_guard);
+
+	return res.map_err(|err| Arc::try_unwrap(err).unwrap_or_else(ErrType::Shared));
+}
+
+///Saca la renovacion de `EN_CURSO` al terminar, aunque el future se cancele, para no dejar esperando a nadie
+struct FinRenovacion(Clave);
+
+impl Drop for FinRenovacion {
+	fn drop(&mut self) {
+		EN_CURSO.remove(&self.0);
+	}
+}
+
+///Identifica un token guardado: de que tenant es, para que webservice y de que ambiente.
+///Un token de homologacion nunca se entrega para un request de produccion, ni al reves
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub struct ServiceId {
 	pub(crate) tenant_id: i64,
 	pub(crate) webservice: Webservice,
+	pub(crate) environment: ArcaEnvironment,
 }
 
-#[derive(Debug)]
+impl ServiceId {
+	pub fn new(tenant_id:i64, webservice:Webservice, environment:ArcaEnvironment) -> Self {
+		Self { tenant_id, webservice, environment }
+	}
+
+	pub fn tenant_id(&self) -> i64 {
+		self.tenant_id
+	}
+
+	pub fn webservice(&self) -> &Webservice {
+		&self.webservice
+	}
+
+	pub fn environment(&self) -> &ArcaEnvironment {
+		&self.environment
+	}
+}
+
+///Ticket de acceso (TA) que devuelve WSAA, con todos los datos del `loginTicketResponse`.
+///
+///Se puede serializar para guardarlo o compartirlo entre procesos. Ver `TokenStore`
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenArca {
-	pub(super) cuit			: i64,
-	pub(super) token		: String,
-	pub(super) sign 		: String,
-	pub(super) expir		: DateTime<Utc>,
+	pub(super) cuit							: i64,
+	pub(super) token						: String,
+	pub(super) sign 						: String,
+	#[serde(default)]
+	pub(super) source						: String,
+	#[serde(default)]
+	pub(super) destination			: String,
+	#[serde(default)]
+	pub(super) unique_id				: u64,
+	#[serde(default)]
+	pub(super) generation_time	: DateTime<FixedOffset>,
+	//Los tokens guardados antes de que existiera el resto de los campos solo traen `expir`
+	#[serde(alias = "expir")]
+	pub(super) expiration_time	: DateTime<FixedOffset>,
+}
+
+impl TokenArca {
+	///CUIT del certificado que firmo el pedido
+	pub fn cuit(&self) -> i64 {
+		self.cuit
+	}
+
+	pub fn token(&self) -> &str {
+		&self.token
+	}
+
+	pub fn sign(&self) -> &str {
+		&self.sign
+	}
+
+	///DN del WSAA que emitio el ticket
+	pub fn source(&self) -> &str {
+		&self.source
+	}
+
+	///DN del certificado para el que se emitio el ticket
+	pub fn destination(&self) -> &str {
+		&self.destination
+	}
+
+	pub fn unique_id(&self) -> u64 {
+		self.unique_id
+	}
+
+	///Tal como la informa ARCA, con su huso horario
+	pub fn generation_time(&self) -> DateTime<FixedOffset> {
+		self.generation_time
+	}
+
+	///Tal como la informa ARCA, con su huso horario
+	pub fn expiration_time(&self) -> DateTime<FixedOffset> {
+		self.expiration_time
+	}
+
+	///`expiration_time` en UTC
+	pub fn expires_at(&self) -> DateTime<Utc> {
+		self.expiration_time.to_utc()
+	}
 }
 
+///Certificado y llave privada en memoria. Firma el ticket con OpenSSL.
+///El certificado puede estar en PEM o DER y la llave en PEM, DER o PKCS#8; el formato se detecta solo
 pub struct CertKeyPair {
 	pub cuit          : i64,
 	pub cert_contents	: Vec<u8>,
 	pub key_contents 	: Vec<u8>,
-}
\ No newline at end of file
+	///Solo si la llave esta encriptada
+	pub key_passphrase: Option<String>,
+}
+
+impl CertKeyPair {
+	///Arma el par desde un `.p12` / `.pfx`, que trae el certificado y la llave juntos
+	pub fn from_pkcs12(cuit:i64, der:&[u8], password:&str) -> Result<Self, CryptoError> {
+		let (cert_contents, key_contents) = pkcs12_to_pem(der, password)?;
+		Ok(Self { cuit, cert_contents, key_contents, key_passphrase: None })
+	}
+}
+
+
+#[cfg(test)]
+impl TokenArca {
+	pub(crate) fn de_prueba(token:&str, expir:DateTime<Utc>) -> Self {
+		TokenArca {
+			cuit						: 20111111112,
+			token						: token.to_owned(),
+			sign						: "sig".to_owned(),
+			source					: String::new(),
+			destination			: String::new(),
+			unique_id				: 1,
+			generation_time	: Utc::now().fixed_offset(),
+			expiration_time	: expir.fixed_offset(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	use crate::{transport::{MemoryTransport, ReplayTransport, SoapResponse}, wsaa::auth_arca::login_response_de_prueba};
+
+	use super::*;
+
+	struct FirmaFalsa;
+
+	impl TraSigner for FirmaFalsa {
+		fn cuit(&self) -> i64 {
+			20111111112
+		}
+
+		fn sign_tra(&self, _tra:&str) -> Result<String, ErrType> {
+			Ok("cms".to_owned())
+		}
+	}
+
+	#[tokio::test]
+	async fn login_sin_red() {
+		let transport = MemoryTransport::new(|_| {
+			let ahora = Utc::now().fixed_offset();
+			Ok(SoapResponse::ok(login_response_de_prueba(&ahora.to_rfc3339(), &(ahora + Duration::hours(12)).to_rfc3339())))
+		});
+		let store = DashMap::new();
+		let key = ServiceId::new(4, Webservice::Wsfev1, ArcaEnvironment::Homologation);
+		let pedir = async || get_token(&store, key.clone(), &transport, &WsaaConfig::default(), async |_| Some(FirmaFalsa), |_, cuit, token, _| format!("{cuit}:{token}")).await;
+
+		assert_eq!(pedir().await.unwrap(), "20111111112:PD94bWwg");
+		assert_eq!(pedir().await.unwrap(), "20111111112:PD94bWwg");
+
+		let requests = transport.requests();
+		assert_eq!(requests.len(), 1);
+		assert!(requests[0].url.contains("wsaahomo"));
+		assert!(requests[0].body.contains("<wsaa:in0>cms</wsaa:in0>"));
+	}
+
+	#[tokio::test]
+	async fn login_con_fixture() {
+		let transport = ReplayTransport::from_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/wsaa_login")).unwrap();
+		let store = DashMap::new();
+		let key = ServiceId::new(5, Webservice::Wsfev1, ArcaEnvironment::Homologation);
+
+		let token = get_token(&store, key.clone(), &transport, &WsaaConfig::default(), async |_| Some(FirmaFalsa), |_, cuit, token, sign| format!("{cuit}:{token}:{sign}")).await;
+		assert_eq!(token.unwrap(), "20111111112:REDACTED:REDACTED");
+		assert_eq!(store.get(&key).unwrap().unique_id(), 3711864213);
+	}
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn renovacion_compartida() {
+		let store = Arc::new(DashMap::<ServiceId, TokenArca>::new());
+		let key = ServiceId::new(2, Webservice::Wsfev1, ArcaEnvironment::Homologation);
+		let logins = Arc::new(AtomicUsize::new(0));
+
+		let tareas: Vec<_> = (0..10).map(|_| {
+			let store = store.clone();
+			let logins = logins.clone();
+			let key = key.clone();
+			tokio::spawn(async move {
+				una_renovacion(&store, key, Duration::minutes(15), async |_| {
+					logins.fetch_add(1, Ordering::SeqCst);
+					tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+					Ok(TokenArca::de_prueba("tok", Utc::now() + Duration::hours(12)))
+				}).await
+			})
+		}).collect();
+
+		for tarea in tareas {
+			assert_eq!(tarea.await.unwrap().unwrap().token, "tok");
+		}
+		assert_eq!(logins.load(Ordering::SeqCst), 1);
+	}
+
+	///Simula otra renovacion que termina justo despues de la primera lectura
+	struct LlegaTarde(AtomicUsize);
+
+	impl TokenStore for LlegaTarde {
+		async fn get(&self, _key:&ServiceId) -> Result<Option<TokenArca>, ErrType> {
+			Ok((self.0.fetch_add(1, Ordering::SeqCst) > 0).then(|| TokenArca::de_prueba("otro", Utc::now() + Duration::hours(12))))
+		}
+
+		async fn put(&self, _key:&ServiceId, _token:&TokenArca) -> Result<(), ErrType> {
+			Ok(())
+		}
+
+		async fn invalidate(&self, _key:&ServiceId) -> Result<(), ErrType> {
+			Ok(())
+		}
+	}
+
+	#[tokio::test]
+	async fn relee_el_store_antes_de_renovar() {
+		let key = ServiceId::new(6, Webservice::Wsfev1, ArcaEnvironment::Homologation);
+		let token = una_renovacion(&LlegaTarde(AtomicUsize::new(0)), key, Duration::minutes(15), async |_| {
+			panic!("No deberia renovar: el store ya tiene un token vigente")
+		}).await;
+		assert_eq!(token.unwrap().token, "otro");
+	}
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn cada_store_renueva_el_suyo() {
+		let stores = [Arc::new(DashMap::<ServiceId, TokenArca>::new()), Arc::new(DashMap::new())];
+		let key = ServiceId::new(7, Webservice::Wsfev1, ArcaEnvironment::Homologation);
+		let logins = Arc::new(AtomicUsize::new(0));
+
+		let tareas: Vec<_> = stores.iter().map(|store| {
+			let store = store.clone();
+			let logins = logins.clone();
+			let key = key.clone();
+			tokio::spawn(async move {
+				una_renovacion(&store, key, Duration::minutes(15), async |_| {
+					logins.fetch_add(1, Ordering::SeqCst);
+					tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+					Ok(TokenArca::de_prueba("tok", Utc::now() + Duration::hours(12)))
+				}).await
+			})
+		}).collect();
+
+		for tarea in tareas {
+			tarea.await.unwrap().unwrap();
+		}
+		assert_eq!(logins.load(Ordering::SeqCst), 2);
+		assert!(stores.iter().all(|store| store.contains_key(&key)));
+	}
+}
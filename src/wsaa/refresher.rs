@@ -0,0 +1,255 @@
+use std::{collections::hash_map::RandomState, future::Future, hash::BuildHasher, pin::Pin, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use tokio::time::Instant;
+
+use crate::{crypto::signer::TraSigner, transport::SoapTransport, types::errors::ErrType};
+
+use super::{config::{AlreadyAuthPolicy, WsaaConfig}, get_token::{get_token, ServiceId}, token_store::TokenStore};
+
+
+type CertSource = Arc<dyn Fn(ServiceId) -> Pin<Box<dyn Future<Output = Option<Box<dyn TraSigner>>> + Send>> + Send + Sync>;
+type OnError = Box<dyn Fn(&ServiceId, &ErrType) + Send + Sync>;
+
+///Demora maxima entre reintentos de un token que viene fallando
+const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+
+///Renueva en segundo plano los tokens de todos los `ServiceId` registrados, antes de que venzan.
+///
+///Usa `get_token` con su propio `WsaaConfig`, asi que comparte el store y la renovacion en curso con las llamadas normales.
+///Conviene que su `refresh_margin` sea bastante mayor que el del resto de la aplicacion, para que el token se renueve aca y no
+///en medio de una factura.
+pub struct TokenRefresher<S, T = reqwest::Client> {
+	token_store		: S,
+	transport			: T,
+	config				: WsaaConfig,
+	check_interval: Duration,
+	registros			: Vec<Registro>,
+	on_error			: Option<OnError>,
+}
+
+struct Registro {
+	key			: ServiceId,
+	source	: CertSource,
+	proximo	: Instant,
+	fallos	: u32,
+}
+
+impl<S: TokenStore, T: SoapTransport> TokenRefresher<S, T> {
+	///`config.already_auth` se ignora: el refresher nunca espera ni reutiliza tokens, se limita a reintentar mas tarde
+	pub fn new(token_store:S, transport:T, config:WsaaConfig) -> Self {
+		let config = WsaaConfig { already_auth: AlreadyAuthPolicy::Fail, ..config };
+		Self {
+			token_store,
+			transport,
+			config,
+			check_interval: Duration::from_secs(5 * 60),
+			registros			: Vec::new(),
+			on_error			: None,
+		}
+	}
+
+	///Cada cuanto se revisan los tokens. Por defecto 5 minutos
+	pub fn check_interval(mut self, check_interval:Duration) -> Self {
+		self.check_interval = check_interval;
+		self
+	}
+
+	///Agrega un `ServiceId` a renovar. `cert_source` cumple la misma funcion que el `cert_key_getter` de `get_token`
+	pub fn register<F, Fut, C>(mut self, key:ServiceId, cert_source:F) -> Self
+	where
+		F: Fn(ServiceId) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Option<C>> + Send + 'static,
+		C: TraSigner + 'static,
+	{
+		let cert_source = Arc::new(cert_source);
+		let source: CertSource = Arc::new(move |key| {
+			let cert_source = cert_source.clone();
+			Box::pin(async move {
+				cert_source(key).await.map(|signer| Box::new(signer) as Box<dyn TraSigner>)
+			})
+		});
+		self.registros.push(Registro { key, source, proximo: Instant::now(), fallos: 0 });
+		self
+	}
+
+	///Se llama cada vez que falla la renovacion de un token
+	pub fn on_error<F>(mut self, on_error:F) -> Self
+	where
+		F: Fn(&ServiceId, &ErrType) + Send + Sync + 'static,
+	{
+		self.on_error = Some(Box::new(on_error));
+		self
+	}
+
+	///Renueva los tokens una vez por `check_interval`, para siempre. Pensado para `tokio::spawn`
+	pub async fn run(mut self) {
+		loop {
+			self.refresh_due().await;
+			tokio::time::sleep(jitter(self.check_interval, 10)).await;
+		}
+	}
+
+	///Revisa una sola vez los tokens que no esten esperando un reintento, renovando los que esten por vencer.
+	///Sirve para precalentar los tokens al iniciar la aplicacion
+	pub async fn refresh_due(&mut self) {
+		let ahora = Instant::now();
+		for reg in self.registros.iter_mut().filter(|reg| reg.proximo <= ahora) {
+			let source = reg.source.clone();
+			let res = get_token(
+				&self.token_store,
+				reg.key.clone(),
+				&self.transport,
+				&self.config,
+				async |key| source(key).await,
+				|_, _, _, _| String::new(),
+			).await;
+
+			match res {
+				Ok(_) => {
+					reg.fallos = 0;
+				},
+				Err(err) => {
+					reg.fallos += 1;
+					//Si otra llamada estaba renovando el mismo token, el error llega compartido
+					let mut causa = &err;
+					while let ErrType::Shared(compartido) = causa {
+						causa = compartido;
+					}
+					reg.proximo = Instant::now() + match causa {
+						ErrType::AlreadyAuthenticated { retry_after } => {
+							jitter((*retry_after - Utc::now()).to_std().unwrap_or_default(), 10)
+						},
+						_ => backoff(reg.fallos),
+					};
+					if let Some(on_error) = &self.on_error {
+						on_error(&reg.key, &err);
+					}
+				},
+			}
+		}
+	}
+}
+
+
+///Demora exponencial desde 1 minuto hasta `MAX_BACKOFF`, con jitter para no sincronizar los reintentos de distintos tenants
+fn backoff(fallos:u32) -> Duration {
+	let base = Duration::from_secs(60).saturating_mul(1 << fallos.saturating_sub(1).min(10));
+	jitter(base.min(MAX_BACKOFF), 20)
+}
+
+///Suma a `dur` hasta un `porcentaje` extra al azar
+fn jitter(dur:Duration, porcentaje:u32) -> Duration {
+	let azar = RandomState::new().hash_one(Instant::now()) % 1000;
+	dur + dur.mul_f64((azar * porcentaje as u64) as f64 / 100_000.0)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+
+	use dashmap::DashMap;
+
+	use crate::{transport::{MemoryTransport, SoapRequest, SoapResponse, TransportError, TransportErrorKind}, types::enums::{ArcaEnvironment, Webservice}, wsaa::{auth_arca::login_response_de_prueba, get_token::TokenArca}};
+
+	use super::*;
+
+	struct FirmaFalsa;
+
+	impl TraSigner for FirmaFalsa {
+		fn cuit(&self) -> i64 {
+			20111111112
+		}
+
+		fn sign_tra(&self, _tra:&str) -> Result<String, ErrType> {
+			Ok("cms".to_owned())
+		}
+	}
+
+	fn login_ok(_req:&SoapRequest) -> Result<SoapResponse, TransportError> {
+		let ahora = Utc::now().fixed_offset();
+		return Ok(SoapResponse::ok(login_response_de_prueba(&ahora.to_rfc3339(), &(ahora + chrono::Duration::hours(12)).to_rfc3339())));
+	}
+
+	///Tarda en responder `coe.alreadyAuthenticated`, para que otra llamada quede esperando la misma renovacion
+	struct YaAutenticadoLento;
+
+	impl SoapTransport for YaAutenticadoLento {
+		async fn send(&self, _req:SoapRequest) -> Result<SoapResponse, TransportError> {
+			tokio::time::sleep(Duration::from_millis(200)).await;
+			let body = "<soapenv:Fault><faultcode>ns1:coe.alreadyAuthenticated</faultcode><faultstring>El CEE ya posee un TA valido</faultstring></soapenv:Fault>";
+			return Ok(SoapResponse { status: 500, headers: Vec::new(), body: body.to_owned() });
+		}
+	}
+
+	#[tokio::test]
+	async fn renueva_los_que_vencen() {
+		let store = Arc::new(DashMap::new());
+		let por_vencer = ServiceId::new(1, Webservice::Wsfev1, ArcaEnvironment::Homologation);
+		let vigente = ServiceId::new(2, Webservice::Wsfev1, ArcaEnvironment::Homologation);
+		store.insert(por_vencer.clone(), TokenArca::de_prueba("viejo", Utc::now() + chrono::Duration::minutes(5)));
+		store.insert(vigente.clone(), TokenArca::de_prueba("vigente", Utc::now() + chrono::Duration::hours(10)));
+
+		let mut refresher = TokenRefresher::new(store.clone(), MemoryTransport::new(login_ok), WsaaConfig::default())
+			.register(por_vencer.clone(), async |_| Some(FirmaFalsa))
+			.register(vigente.clone(), async |_| Some(FirmaFalsa));
+		refresher.refresh_due().await;
+
+		assert_eq!(refresher.transport.requests().len(), 1);
+		assert_eq!(store.as_ref().get(&por_vencer).unwrap().token(), "PD94bWwg");
+		assert_eq!(store.as_ref().get(&vigente).unwrap().token(), "vigente");
+	}
+
+	#[tokio::test]
+	async fn avisa_los_errores() {
+		let key = ServiceId::new(3, Webservice::Wsfev1, ArcaEnvironment::Homologation);
+		let errores = Arc::new(Mutex::new(Vec::new()));
+		let avisados = errores.clone();
+		let sin_red = MemoryTransport::new(|_| Err(TransportError::new(TransportErrorKind::Connect, "connection refused")));
+
+		let mut refresher = TokenRefresher::new(DashMap::new(), sin_red, WsaaConfig::default())
+			.register(key.clone(), async |_| Some(FirmaFalsa))
+			.on_error(move |key, err| avisados.lock().unwrap().push((key.clone(), matches!(err, ErrType::Transport(_)))));
+		refresher.refresh_due().await;
+
+		assert_eq!(*errores.lock().unwrap(), vec![(key, true)]);
+		assert_eq!(refresher.registros[0].fallos, 1);
+		assert!(refresher.registros[0].proximo >= Instant::now() + Duration::from_secs(59));
+	}
+
+	#[tokio::test]
+	async fn espera_retry_after_aunque_la_renovacion_sea_compartida() {
+		let store = Arc::new(DashMap::new());
+		let key = ServiceId::new(4, Webservice::Wsfev1, ArcaEnvironment::Homologation);
+
+		let compartido = Arc::new(Mutex::new(false));
+		let visto = compartido.clone();
+		let mut refresher = TokenRefresher::new(store.clone(), YaAutenticadoLento, WsaaConfig::default())
+			.register(key.clone(), async |_| Some(FirmaFalsa))
+			.on_error(move |_, err| *visto.lock().unwrap() = matches!(err, ErrType::Shared(_)));
+
+		//La primera llamada hace el login; el refresher espera su resultado y lo recibe como `ErrType::Shared`
+		let config = WsaaConfig::default();
+		let (primera, _) = tokio::join!(
+			get_token(&store, key, &YaAutenticadoLento, &config, async |_| Some(FirmaFalsa), |_, _, _, _| String::new()),
+			async {
+				tokio::time::sleep(Duration::from_millis(50)).await;
+				refresher.refresh_due().await;
+			},
+		);
+
+		assert!(matches!(primera, Err(ErrType::AlreadyAuthenticated { .. }) | Err(ErrType::Shared(_))), "{primera:?}");
+		assert!(*compartido.lock().unwrap());
+		//`retry_after` son 10 minutos; el backoff del primer fallo no llega a 2
+		assert!(refresher.registros[0].proximo >= Instant::now() + Duration::from_secs(9 * 60));
+	}
+
+	#[test]
+	fn backoff_acotado() {
+		assert!(backoff(1) >= Duration::from_secs(60) && backoff(1) <= Duration::from_secs(72));
+		assert!(backoff(3) >= Duration::from_secs(240));
+		assert!(backoff(50) <= MAX_BACKOFF + MAX_BACKOFF / 5);
+	}
+}
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+use openssl::base64::decode_block;
+
+use crate::{types::errors::{ErrType, SoapFault}, xml_utils::{get_tag_attr, get_xml_attr, get_xml_open_tags}};
+
+use super::get_token::TokenArca;
+
+
+///Contenido del `token` de WSAA, que es un ticket SSO en base64.
+///
+///Dice quien se autentico, para que servicio y en nombre de que CUITs puede operar
+#[derive(Debug, Clone)]
+pub struct SsoTicket {
+	///DN del WSAA que emitio el ticket
+	pub issuer					: String,
+	///DN del servicio para el que vale el ticket
+	pub destination			: String,
+	pub unique_id				: u64,
+	pub generation_time	: DateTime<Utc>,
+	pub expiration_time	: DateTime<Utc>,
+	///Nombre del servicio, por ejemplo `wsfe`
+	pub service					: String,
+	///DN del certificado que se autentico
+	pub uid							: String,
+	///CUIT del `SERIALNUMBER` de `uid`
+	pub cuit						: Option<i64>,
+	///CUITs que el certificado puede representar, incluido el propio
+	pub relations				: Vec<SsoRelation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SsoRelation {
+	pub cuit		: i64,
+	///Tipo de relacion segun ARCA. `4` es la delegacion del servicio al computador fiscal. Vacio si el ticket no lo trae
+	pub reltype	: String,
+}
+
+impl SsoTicket {
+	///Decodifica el `token` de un `TokenArca`
+	pub fn decode(token:&str) -> Result<Self, ErrType> {
+		let bytes = decode_block(token.trim())
+			.map_err(|_| SoapFault::new("parseError", "El token de WSAA no es base64 valido"))?;
+		let xml = String::from_utf8(bytes)
+			.map_err(|_| SoapFault::new("parseError", "El token de WSAA no es UTF-8"))?;
+
+		let attr = |tag:&str, attr:&str| get_xml_attr(&xml, tag, attr)
+			.ok_or(SoapFault::new("parseError", &format!("No se encontro {tag}.{attr} en el token de WSAA")));
+		let numero = |tag:&str, name:&str| -> Result<i64, SoapFault> {
+			let valor = attr(tag, name)?;
+			return valor.trim().parse()
+				.map_err(|_| SoapFault::new("parseError", &format!("{tag}.{name} invalido en el token de WSAA ({valor})")));
+		};
+		let fecha = |tag:&str, name:&str| -> Result<DateTime<Utc>, SoapFault> {
+			return DateTime::from_timestamp(numero(tag, name)?, 0)
+				.ok_or(SoapFault::new("parseError", &format!("{tag}.{name} fuera de rango en el token de WSAA")));
+		};
+
+		let uid = attr("login", "uid")?;
+		//Cada relacion se lee entera: si a una le falta `reltype`, queda vacio en vez de correr al resto
+		let relations = get_xml_open_tags(&xml, "relation").into_iter()
+			.filter_map(|rel| Some(SsoRelation {
+				cuit		: get_tag_attr(rel, "key")?.trim().parse().ok()?,
+				reltype	: get_tag_attr(rel, "reltype").unwrap_or_default(),
+			}))
+			.collect();
+
+		return Ok(SsoTicket {
+			issuer					: attr("id", "src")?,
+			destination			: attr("id", "dst")?,
+			unique_id				: numero("id", "unique_id")? as u64,
+			generation_time	: fecha("id", "gen_time")?,
+			expiration_time	: fecha("id", "exp_time")?,
+			service					: attr("login", "service")?,
+			cuit						: cuit_de_dn(&uid),
+			uid,
+			relations,
+		});
+	}
+
+	///Si el certificado puede operar en nombre de `cuit`, por ser el propio o por tener la delegacion
+	pub fn can_represent(&self, cuit:i64) -> bool {
+		return self.cuit == Some(cuit) || self.relations.iter().any(|rel| rel.cuit == cuit);
+	}
+}
+
+impl TokenArca {
+	///Decodifica el `token`. Ver `SsoTicket`
+	pub fn sso(&self) -> Result<SsoTicket, ErrType> {
+		return SsoTicket::decode(&self.token);
+	}
+}
+
+///Busca `SERIALNUMBER=CUIT <n>` dentro de un DN
+fn cuit_de_dn(dn:&str) -> Option<i64> {
+	return dn.split(',')
+		.filter_map(|part| part.trim().split_once('='))
+		.find(|(key, _)| key.trim().eq_ignore_ascii_case("serialNumber"))
+		.and_then(|(_, value)| value.trim().trim_start_matches("CUIT").trim().parse().ok());
+}
+
+
+#[cfg(test)]
+mod tests {
+	use openssl::base64::encode_block;
+
+	use super::*;
+
+	#[test]
+	fn decodifica_relaciones() {
+		let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<sso version="2.0">
+    <id src="CN=wsaahomo, O=AFIP, C=AR, SERIALNUMBER=CUIT 33693450239" dst="CN=wsfe, O=AFIP, C=AR" unique_id="1234567890" gen_time="1792328400" exp_time="1792371600"/>
+    <operation type="login" value="granted">
+        <login entity="33693450239" service="wsfe" uid="SERIALNUMBER=CUIT 20111111112, CN=facturador" authmethod="cms" regmethod="22">
+            <relations>
+                <relation key="20111111112" reltype="4"/>
+                <relation key="30712345678" reltype="4"/>
+            </relations>
+        </login>
+    </operation>
+</sso>"#;
+
+		let sso = SsoTicket::decode(&encode_block(xml.as_bytes())).unwrap();
+		assert_eq!(sso.service, "wsfe");
+		assert_eq!(sso.cuit, Some(20111111112));
+		assert_eq!(sso.expiration_time.timestamp(), 1792371600);
+		assert_eq!(sso.relations, vec![
+			SsoRelation { cuit: 20111111112, reltype: "4".to_owned() },
+			SsoRelation { cuit: 30712345678, reltype: "4".to_owned() },
+		]);
+		assert!(sso.can_represent(30712345678));
+		assert!(!sso.can_represent(30999999999));
+	}
+
+	#[test]
+	fn relacion_sin_reltype() {
+		let xml = r#"<sso version="2.0">
+    <id src="CN=wsaahomo" dst="CN=wsfe" unique_id="1" gen_time="1792328400" exp_time="1792371600"/>
+    <operation type="login" value="granted">
+        <login service="wsfe" uid="SERIALNUMBER=CUIT 20111111112, CN=facturador">
+            <relations>
+                <relation key="20111111112"/>
+                <relation key="30712345678" reltype="4"/>
+                <relation key="30799999999" reltype="4"/>
+            </relations>
+        </login>
+    </operation>
+</sso>"#;
+
+		let sso = SsoTicket::decode(&encode_block(xml.as_bytes())).unwrap();
+		assert_eq!(sso.relations, vec![
+			SsoRelation { cuit: 20111111112, reltype: String::new() },
+			SsoRelation { cuit: 30712345678, reltype: "4".to_owned() },
+			SsoRelation { cuit: 30799999999, reltype: "4".to_owned() },
+		]);
+	}
+}
@@ -0,0 +1,176 @@
+use std::{future::Future, io::ErrorKind, path::PathBuf, sync::{atomic::{AtomicU64, Ordering}, Arc}};
+
+use dashmap::DashMap;
+
+use crate::types::errors::ErrType;
+
+use super::get_token::{ServiceId, TokenArca};
+
+/// Donde `get_token` guarda y busca los tokens de WSAA.
+///
+/// Implementalo sobre tu base de datos o cache compartida si queres que los tokens sobrevivan a un reinicio
+/// o que varios procesos usen el mismo token.
+pub trait TokenStore: Send + Sync {
+	/// Devuelve el token guardado para `key`, este vencido o no. `get_token` decide si todavia sirve
+	fn get(&self, key:&ServiceId) -> impl Future<Output = Result<Option<TokenArca>, ErrType>> + Send;
+
+	/// Guarda el token, reemplazando el anterior si existia
+	fn put(&self, key:&ServiceId, token:&TokenArca) -> impl Future<Output = Result<(), ErrType>> + Send;
+
+	/// Borra el token guardado, forzando que el proximo `get_token` lo renueve
+	fn invalidate(&self, key:&ServiceId) -> impl Future<Output = Result<(), ErrType>> + Send;
+
+	/// Identifica al store en las renovaciones en curso: solo las llamadas con el mismo store comparten el login.
+	/// Por defecto es la direccion del store. Los que envuelven a otro, como `Arc`, devuelven la del envuelto
+	fn store_id(&self) -> usize {
+		self as *const Self as *const () as usize
+	}
+}
+
+/// Guarda los tokens en memoria. Se pierden al reiniciar el proceso
+impl TokenStore for DashMap<ServiceId, TokenArca> {
+	async fn get(&self, key:&ServiceId) -> Result<Option<TokenArca>, ErrType> {
+		Ok(DashMap::get(self, key).map(|rf| rf.value().clone()))
+	}
+
+	async fn put(&self, key:&ServiceId, token:&TokenArca) -> Result<(), ErrType> {
+		self.insert(key.clone(), token.clone());
+		Ok(())
+	}
+
+	async fn invalidate(&self, key:&ServiceId) -> Result<(), ErrType> {
+		self.remove(key);
+		Ok(())
+	}
+}
+
+impl<S: TokenStore> TokenStore for Arc<S> {
+	fn get(&self, key:&ServiceId) -> impl Future<Output = Result<Option<TokenArca>, ErrType>> + Send {
+		self.as_ref().get(key)
+	}
+
+	fn put(&self, key:&ServiceId, token:&TokenArca) -> impl Future<Output = Result<(), ErrType>> + Send {
+		self.as_ref().put(key, token)
+	}
+
+	fn invalidate(&self, key:&ServiceId) -> impl Future<Output = Result<(), ErrType>> + Send {
+		self.as_ref().invalidate(key)
+	}
+
+	fn store_id(&self) -> usize {
+		self.as_ref().store_id()
+	}
+}
+
+
+/// Guarda cada token como un archivo json dentro de `dir`.
+///
+/// Los tokens sobreviven a un reinicio y pueden compartirse entre procesos que apunten al mismo directorio.
+/// La escritura se hace sobre un archivo temporal que luego se renombra, asi nadie lee un token a medio escribir.
+///
+/// Lee y escribe con `std::fs`, bloqueando el thread mientras tanto: son archivos chicos y se tocan solo al renovar,
+/// y asi el store funciona sin runtime, como en `crate::blocking`.
+#[derive(Debug, Clone)]
+pub struct FileTokenStore {
+	dir: PathBuf,
+}
+
+impl FileTokenStore {
+	/// Crea el directorio si no existe
+	pub fn new(dir: impl Into<PathBuf>) -> Result<Self, ErrType> {
+		let dir = dir.into();
+		std::fs::create_dir_all(&dir)?;
+		Ok(Self { dir })
+	}
+
+	fn path(&self, key:&ServiceId) -> PathBuf {
+		//Los servicios y ambientes `Custom` pueden traer cualquier caracter, y el nombre tiene que servir como archivo
+		let limpiar = |nombre:&str| -> String {
+			nombre.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' {c} else {'-'}).collect()
+		};
+		let servicio = limpiar(key.webservice.service_name());
+		let ambiente = limpiar(key.environment.name());
+		self.dir.join(format!("{}_{servicio}_{ambiente}.json", key.tenant_id))
+	}
+}
+
+impl TokenStore for FileTokenStore {
+	async fn get(&self, key:&ServiceId) -> Result<Option<TokenArca>, ErrType> {
+		match std::fs::read(self.path(key)) {
+			Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+			Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+			Err(err) => Err(err.into()),
+		}
+	}
+
+	async fn put(&self, key:&ServiceId, token:&TokenArca) -> Result<(), ErrType> {
+		let path = self.path(key);
+		//Unico por proceso y por llamada, para que dos `put` simultaneos no escriban el mismo temporal
+		static TEMPORALES: AtomicU64 = AtomicU64::new(0);
+		let tmp = path.with_extension(format!("{}.{}.tmp", std::process::id(), TEMPORALES.fetch_add(1, Ordering::Relaxed)));
+		std::fs::write(&tmp, serde_json::to_vec(token)?)?;
+		std::fs::rename(&tmp, &path)?;
+		Ok(())
+	}
+
+	async fn invalidate(&self, key:&ServiceId) -> Result<(), ErrType> {
+		match std::fs::remove_file(self.path(key)) {
+			Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+			_ => Ok(()),
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use chrono::{Duration, Utc};
+
+	use crate::types::enums::{ArcaEnvironment, Webservice};
+
+	use super::*;
+
+	#[tokio::test]
+	async fn file_store_roundtrip() {
+		let dir = std::env::temp_dir().join(format!("arca_token_store_{}", std::process::id()));
+		let store = FileTokenStore::new(&dir).unwrap();
+		let key = ServiceId::new(1, Webservice::Wsfev1, ArcaEnvironment::Production);
+		let token = TokenArca::de_prueba("tok", Utc::now() + Duration::hours(1));
+
+		assert!(store.get(&key).await.unwrap().is_none());
+		store.put(&key, &token).await.unwrap();
+		assert!(store.get(&ServiceId::new(1, Webservice::Wsfev1, ArcaEnvironment::Homologation)).await.unwrap().is_none());
+
+		let leido = FileTokenStore::new(&dir).unwrap().get(&key).await.unwrap().unwrap();
+		assert_eq!(leido.token, token.token);
+		assert_eq!(leido.expiration_time, token.expiration_time);
+
+		//Formato anterior, con solo `expir`
+		std::fs::write(store.path(&key), r#"{"cuit":1,"token":"tok","sign":"sig","expir":"2026-01-01T00:00:00Z"}"#).unwrap();
+		assert_eq!(store.get(&key).await.unwrap().unwrap().expires_at().to_rfc3339(), "2026-01-01T00:00:00+00:00");
+
+		store.invalidate(&key).await.unwrap();
+		assert!(store.get(&key).await.unwrap().is_none());
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn file_store_puts_simultaneos() {
+		let dir = std::env::temp_dir().join(format!("arca_token_store_puts_{}", std::process::id()));
+		let store = Arc::new(FileTokenStore::new(&dir).unwrap());
+		let key = ServiceId::new(1, Webservice::Wsfev1, ArcaEnvironment::Production);
+
+		let tareas: Vec<_> = (0..20).map(|n| {
+			let store = store.clone();
+			let key = key.clone();
+			tokio::spawn(async move { store.put(&key, &TokenArca::de_prueba(&format!("tok{n}"), Utc::now() + Duration::hours(1))).await })
+		}).collect();
+		for tarea in tareas {
+			tarea.await.unwrap().unwrap();
+		}
+
+		assert!(store.get(&key).await.unwrap().unwrap().token.starts_with("tok"));
+		assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
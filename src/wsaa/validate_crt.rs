@@ -1,15 +1,74 @@
-use openssl::pkey::PKey;
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+use openssl::hash::MessageDigest;
+use openssl::pkey::{Id, PKey};
+use openssl::stack::Stack;
+use openssl::x509::store::{X509Store, X509StoreBuilder};
+use openssl::x509::verify::X509VerifyParam;
+use openssl::x509::X509StoreContext;
 use openssl::{pkey::Private, x509::X509};
 use openssl::nid::Nid;
 use chrono::{DateTime, Utc, NaiveDateTime};
 
+use crate::{crypto::load::{parse_cert, parse_key, parse_pkcs12}, types::{enums::ArcaEnvironment, errors::CryptoError}};
 
-#[derive(Debug)]
+use super::generate_csr::MIN_KEY_BITS;
+
+
+// Codigos de error de `X509_verify_cert` (x509_vfy.h) que el crate openssl no expone
+const X509_V_ERR_CERT_NOT_YET_VALID: i32 = 9;
+const X509_V_ERR_CERT_HAS_EXPIRED: i32 = 10;
+
+/// Por debajo de estos dias para el vencimiento se agrega `CertWarning::ExpiresSoon`
+pub const EXPIRY_WARNING_DAYS: i64 = 30;
+
+#[derive(Debug, Default)]
 pub struct CertInfo {
     pub cert_venci: Option<DateTime<Utc>>,
+    pub cert_desde: Option<DateTime<Utc>>,
+    /// Dias enteros hasta `cert_venci`, contados desde el `now` de la inspeccion. Negativo si ya vencio
+    pub days_to_expiry: Option<i64>,
     pub issuer_cn: Option<String>,
     pub subject_serial: Option<String>,
+    /// Razon social (`O`)
+    pub subject_o: Option<String>,
+    /// Alias del certificado en ARCA (`CN`)
+    pub subject_cn: Option<String>,
+    /// `RSA`, `EC`, etc
+    pub key_algorithm: Option<String>,
+    pub key_bits: Option<u32>,
+    /// Numero de serie del certificado, en hexa
+    pub serial_number: Option<String>,
+    /// Huellas del certificado en DER, en hexa separado por `:`
+    pub sha1_fingerprint: Option<String>,
+    pub sha256_fingerprint: Option<String>,
     pub errors: Vec<CertError>,
+    /// Problemas que no impiden usar el certificado
+    pub warnings: Vec<CertWarning>,
+}
+
+#[derive(Debug)]
+pub enum CertWarning {
+    ExpiresSoon { days: i64 },
+    WeakKey { bits: u32 },
+}
+
+impl CertWarning {
+    /// Codigo estable para identificar la advertencia sin depender del texto
+    pub fn code(&self) -> &'static str {
+        match self {
+            CertWarning::ExpiresSoon { .. } => "CERT_EXPIRES_SOON",
+            CertWarning::WeakKey { .. }     => "CERT_WEAK_KEY",
+        }
+    }
+}
+
+impl std::fmt::Display for CertWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CertWarning::ExpiresSoon { days } => write!(f, "El certificado vence en {days} dias"),
+            CertWarning::WeakKey { bits }     => write!(f, "La llave tiene {bits} bits. ARCA exige al menos {MIN_KEY_BITS} para certificados nuevos"),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -23,52 +82,158 @@ pub enum CertError {
     InvalidDate,
 		InvalidPrivateKey,
     KeyMismatch,
+    InvalidPkcs12,
+    PassphraseRequired,
+    WrongPassphrase,
+    Expired,
+    NotYetValid,
+    UntrustedChain,
+    MissingTrustAnchors,
 }
 
 impl CertError {
-	pub fn to_string(&self) -> String {
-		match self {
-				CertError::InvalidPem          => "Certificado Invalido".to_string(),
-				CertError::MissingSerialNumber => "No se encontro el CUIT".to_string(),
-				CertError::InvalidIdentidad    => "El certificado no pertenece a esta empresa".to_string(),
-				CertError::MissingCN           => "No se pudo identificar al emisor del certificado".to_string(),
-				CertError::MultipleCN          => "El certificado posee mas de un emisor".to_string(),
-				CertError::InvalidCN           => "El emisor del certificado no es el esperado. Se espera 'Computadores' para produccion y 'Computadores Test' para homologacion".to_string(),
-				CertError::InvalidDate         => "No se pudo leer la fecha de vencimiento del certificado".to_string(),
-				CertError::InvalidPrivateKey   => "No se pudo leer la llave privada del certificado".to_string(),
-				CertError::KeyMismatch         => "La llave privada no coincide con el certificado".to_string(),
-		}
-	}
-}
-
-pub fn inspect_cert(cert_pem: &str, es_prod: bool, cuit: i64, private_key_pem: &str) -> CertInfo {
-    let mut errors = Vec::new();
+    /// Codigo estable para identificar el error sin depender del texto
+    pub fn code(&self) -> &'static str {
+        match self {
+            CertError::InvalidPem          => "CERT_INVALID",
+            CertError::MissingSerialNumber => "CERT_MISSING_CUIT",
+            CertError::InvalidIdentidad    => "CERT_WRONG_CUIT",
+            CertError::MissingCN           => "CERT_MISSING_ISSUER",
+            CertError::MultipleCN          => "CERT_MULTIPLE_ISSUERS",
+            CertError::InvalidCN           => "CERT_WRONG_ISSUER",
+            CertError::InvalidDate         => "CERT_INVALID_DATE",
+            CertError::InvalidPrivateKey   => "KEY_INVALID",
+            CertError::KeyMismatch         => "KEY_MISMATCH",
+            CertError::InvalidPkcs12       => "PKCS12_INVALID",
+            CertError::PassphraseRequired  => "KEY_PASSPHRASE_REQUIRED",
+            CertError::WrongPassphrase     => "KEY_WRONG_PASSPHRASE",
+            CertError::Expired             => "CERT_EXPIRED",
+            CertError::NotYetValid         => "CERT_NOT_YET_VALID",
+            CertError::UntrustedChain      => "CERT_UNTRUSTED_CHAIN",
+            CertError::MissingTrustAnchors => "CERT_MISSING_TRUST_ANCHORS",
+        }
+    }
+}
+
+impl std::fmt::Display for CertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            CertError::InvalidPem          => "Certificado Invalido",
+            CertError::MissingSerialNumber => "No se encontro el CUIT",
+            CertError::InvalidIdentidad    => "El certificado no pertenece a esta empresa",
+            CertError::MissingCN           => "No se pudo identificar al emisor del certificado",
+            CertError::MultipleCN          => "El certificado posee mas de un emisor",
+            CertError::InvalidCN           => "El emisor del certificado no es el esperado. Se espera 'Computadores' para produccion y 'Computadores Test' para homologacion",
+            CertError::InvalidDate         => "No se pudieron leer las fechas de vigencia del certificado",
+            CertError::InvalidPrivateKey   => "No se pudo leer la llave privada del certificado",
+            CertError::KeyMismatch         => "La llave privada no coincide con el certificado",
+            CertError::InvalidPkcs12       => "No se pudo abrir el archivo PKCS#12 / PFX. Verifique la contraseña",
+            CertError::PassphraseRequired  => "La llave privada esta encriptada. Indique su contraseña",
+            CertError::WrongPassphrase     => "La contraseña de la llave privada es incorrecta",
+            CertError::Expired             => "El certificado esta vencido",
+            CertError::NotYetValid         => "El certificado todavia no esta vigente",
+            CertError::UntrustedChain      => "El certificado no fue emitido por la autoridad certificante de ARCA del ambiente elegido",
+            CertError::MissingTrustAnchors => "No hay certificados de ARCA configurados para verificar la cadena en este ambiente",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl std::error::Error for CertError {}
 
+/// Certificados de las autoridades certificantes de ARCA contra los que se verifica la cadena, por ambiente
+#[derive(Clone)]
+pub struct ArcaTrust {
+    prod: Vec<X509>,
+    homo: Vec<X509>,
+}
+
+impl ArcaTrust {
+    /// Los certificados de ARCA incluidos en la libreria, en `src/wsaa/ca`.
+    /// Falla con `CryptoError::InvalidCert` si alguno de esos PEM no se puede leer o no tiene certificados
+    pub fn bundled() -> Result<Self, CryptoError> {
+        let load = |archivo: &str, pem: &str| {
+            let certs = X509::stack_from_pem(pem.as_bytes()).map_err(|err| CryptoError::InvalidCert(format!("src/wsaa/ca/{archivo}: {err}")))?;
+            if certs.is_empty() {
+                return Err(CryptoError::InvalidCert(format!("src/wsaa/ca/{archivo} no tiene certificados de ARCA")));
+            }
+            Ok(certs)
+        };
+        Ok(ArcaTrust {
+            prod: load("produccion.pem", include_str!("ca/produccion.pem"))?,
+            homo: load("homologacion.pem", include_str!("ca/homologacion.pem"))?,
+        })
+    }
+
+    /// Certificados propios en PEM, uno o varios por ambiente. Reemplazan a los incluidos
+    pub fn from_pem(prod: &[u8], homo: &[u8]) -> Result<Self, CryptoError> {
+        let load = |pem: &[u8]| X509::stack_from_pem(pem).map_err(|err| CryptoError::InvalidCert(err.to_string()));
+        Ok(ArcaTrust { prod: load(prod)?, homo: load(homo)? })
+    }
+
+    fn store(&self, es_prod: bool, now: DateTime<Utc>) -> Option<X509Store> {
+        let anchors = if es_prod { &self.prod } else { &self.homo };
+        if anchors.is_empty() {
+            return None;
+        }
+        let mut builder = X509StoreBuilder::new().ok()?;
+        for ca in anchors {
+            builder.add_cert(ca.clone()).ok()?;
+        }
+        let mut param = X509VerifyParam::new().ok()?;
+        param.set_time(now.timestamp() as _);
+        builder.set_param(&param).ok()?;
+        Some(builder.build())
+    }
+}
+
+/// Valida el certificado y la llave privada de un tenant.
+/// Ambos pueden venir en PEM o DER, y la llave tambien en PKCS#8. El formato se detecta solo.
+/// `key_passphrase` solo hace falta si la llave esta encriptada.
+///
+/// La cadena se verifica contra `trust` y la vigencia contra `now`, normalmente `Utc::now()`
+pub fn inspect_cert(cert: impl AsRef<[u8]>, environment: &ArcaEnvironment, cuit: i64, private_key: impl AsRef<[u8]>, key_passphrase: Option<&str>, trust: &ArcaTrust, now: DateTime<Utc>) -> CertInfo {
     // -------------------------
     // Parse certificate
     // -------------------------
-    let cert = match X509::from_pem(cert_pem.as_bytes()) {
+    let cert = match parse_cert(cert.as_ref()) {
         Ok(c) => c,
-        Err(_) => {
-            return CertInfo {
-                cert_venci: None,
-                issuer_cn: None,
-                subject_serial: None,
-                errors: vec![CertError::InvalidPem],
-            };
-        }
+        Err(_) => return CertInfo::invalid(CertError::InvalidPem),
     };
 
 		// -------------------------
     // Parse private key
     // -------------------------
-    let private_key: Option<PKey<Private>> = match PKey::private_key_from_pem(private_key_pem.as_bytes()) {
-        Ok(k) => Some(k),
-        Err(_) => {
-            errors.push(CertError::InvalidPrivateKey);
-            None
+    let private_key = parse_key(private_key.as_ref(), key_passphrase).map_err(|err| match err {
+        CryptoError::PassphraseRequired => CertError::PassphraseRequired,
+        CryptoError::WrongPassphrase    => CertError::WrongPassphrase,
+        _                               => CertError::InvalidPrivateKey,
+    });
+
+    inspect(cert, private_key, environment.es_prod(), cuit, trust, now)
+}
+
+/// Igual que `inspect_cert`, pero para un `.p12` / `.pfx` que trae el certificado y la llave juntos
+pub fn inspect_pkcs12(der: &[u8], password: &str, environment: &ArcaEnvironment, cuit: i64, trust: &ArcaTrust, now: DateTime<Utc>) -> CertInfo {
+    match parse_pkcs12(der, password) {
+        Ok((cert, key)) => inspect(cert, Ok(key), environment.es_prod(), cuit, trust, now),
+        Err(_) => CertInfo::invalid(CertError::InvalidPkcs12),
+    }
+}
+
+impl CertInfo {
+    fn invalid(error: CertError) -> Self {
+        CertInfo {
+            errors: vec![error],
+            ..Default::default()
         }
-    };
+    }
+}
+
+fn inspect(cert: X509, private_key: Result<PKey<Private>, CertError>, es_prod: bool, cuit: i64, trust: &ArcaTrust, now: DateTime<Utc>) -> CertInfo {
+    let mut errors = Vec::new();
+
+    let private_key = private_key.map_err(|err| errors.push(err)).ok();
 
     // -------------------------
     // Validate key matches cert
@@ -92,10 +257,10 @@ pub fn inspect_cert(cert_pem: &str, es_prod: bool, cuit: i64, private_key_pem: &
 
     let serials: Vec<String> = subject
         .entries_by_nid(Nid::SERIALNUMBER)
-        .filter_map(|e| e.data().as_utf8().ok().map(|s| s.to_string()))
+        .map(|e| String::from_utf8_lossy(e.data().as_slice()).into_owned())
         .collect();
 
-    let subject_serial = serials.get(0).cloned();
+    let subject_serial = serials.first().cloned();
 
     if serials.is_empty() {
         errors.push(CertError::MissingSerialNumber);
@@ -114,10 +279,10 @@ pub fn inspect_cert(cert_pem: &str, es_prod: bool, cuit: i64, private_key_pem: &
 
     let cns: Vec<String> = issuer
         .entries_by_nid(Nid::COMMONNAME)
-        .filter_map(|e| e.data().as_utf8().ok().map(|s| s.to_string()))
+        .map(|e| String::from_utf8_lossy(e.data().as_slice()).into_owned())
         .collect();
 
-    let issuer_cn = cns.get(0).cloned();
+    let issuer_cn = cns.first().cloned();
 
     match cns.len() {
         0 => errors.push(CertError::MissingCN),
@@ -138,25 +303,166 @@ pub fn inspect_cert(cert_pem: &str, es_prod: bool, cuit: i64, private_key_pem: &
     // -------------------------
     // Expiration date
     // -------------------------
-    let cert_venci = {
-        let not_after = cert.not_after().to_string();
-
-        match NaiveDateTime::parse_from_str(
-            &not_after,
-            "%b %e %H:%M:%S %Y GMT"
-        ) {
-            Ok(naive) => Some(DateTime::<Utc>::from_naive_utc_and_offset(naive, Utc)),
-            Err(_) => {
-                errors.push(CertError::InvalidDate);
-                None
+    let cert_venci = asn1_to_utc(cert.not_after());
+    let cert_desde = asn1_to_utc(cert.not_before());
+    if cert_venci.is_none() || cert_desde.is_none() {
+        errors.push(CertError::InvalidDate);
+    }
+    let days_to_expiry = cert_venci.map(|venci| (venci - now).num_days());
+
+    // -------------------------
+    // Validity against `now`
+    // -------------------------
+    if let Ok(ahora) = Asn1Time::from_unix(now.timestamp()) {
+        if cert.not_after() < ahora {
+            errors.push(CertError::Expired);
+        }
+        if cert.not_before() > ahora {
+            errors.push(CertError::NotYetValid);
+        }
+    }
+
+    // -------------------------
+    // Chain against ARCA CAs
+    // -------------------------
+    match trust.store(es_prod, now) {
+        None => errors.push(CertError::MissingTrustAnchors),
+        Some(store) => {
+            if let Some(err) = verify_chain(&store, &cert) {
+                errors.push(err);
             }
         }
-    };
+    }
+
+    // -------------------------
+    // Details for display
+    // -------------------------
+    let first_entry = |nid| cert.subject_name().entries_by_nid(nid).next()
+        .map(|e| String::from_utf8_lossy(e.data().as_slice()).into_owned());
+    let public_key = cert.public_key().ok();
+    let fingerprint = |md| cert.digest(md).ok()
+        .map(|d| d.iter().map(|b| format!("{b:02X}")).collect::<Vec<_>>().join(":"));
+
+    let mut warnings = Vec::new();
+    if let Some(days) = days_to_expiry && (0..EXPIRY_WARNING_DAYS).contains(&days) {
+        warnings.push(CertWarning::ExpiresSoon { days });
+    }
+    if let Some(pk) = &public_key && pk.id() == Id::RSA && pk.bits() < MIN_KEY_BITS {
+        warnings.push(CertWarning::WeakKey { bits: pk.bits() });
+    }
 
     CertInfo {
         cert_venci,
+        cert_desde,
+        days_to_expiry,
         issuer_cn,
         subject_serial,
+        subject_o: first_entry(Nid::ORGANIZATIONNAME),
+        subject_cn: first_entry(Nid::COMMONNAME),
+        key_algorithm: public_key.as_ref().map(|pk| key_algorithm(pk.id())),
+        key_bits: public_key.as_ref().map(|pk| pk.bits()),
+        serial_number: cert.serial_number().to_bn().and_then(|bn| bn.to_hex_str()).ok().map(|s| s.to_string()),
+        sha1_fingerprint: fingerprint(MessageDigest::sha1()),
+        sha256_fingerprint: fingerprint(MessageDigest::sha256()),
         errors,
+        warnings,
     }
-}
\ No newline at end of file
+}
+
+fn asn1_to_utc(time: &Asn1TimeRef) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(&time.to_string(), "%b %e %H:%M:%S %Y GMT")
+        .ok()
+        .map(|naive| DateTime::<Utc>::from_naive_utc_and_offset(naive, Utc))
+}
+
+fn key_algorithm(id: Id) -> String {
+    match id {
+        Id::RSA     => "RSA".to_string(),
+        Id::EC      => "EC".to_string(),
+        Id::DSA     => "DSA".to_string(),
+        Id::ED25519 => "Ed25519".to_string(),
+        other       => format!("{other:?}"),
+    }
+}
+
+/// La vigencia del propio certificado ya se reporta aparte, cualquier otra falla es de la cadena
+fn verify_chain(store: &X509Store, cert: &X509) -> Option<CertError> {
+    let chain = Stack::new().ok()?;
+    let mut ctx = X509StoreContext::new().ok()?;
+    let res = ctx.init(store, cert, &chain, |ctx| {
+        if ctx.verify_cert()? {
+            return Ok(None);
+        }
+        let vigencia = matches!(ctx.error().as_raw(), X509_V_ERR_CERT_NOT_YET_VALID | X509_V_ERR_CERT_HAS_EXPIRED);
+        Ok((!vigencia || ctx.error_depth() != 0).then_some(CertError::UntrustedChain))
+    });
+    res.unwrap_or(Some(CertError::UntrustedChain))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use openssl::{hash::MessageDigest, rsa::Rsa, x509::X509NameBuilder};
+
+    use super::*;
+
+    const CUIT: i64 = 20111111112;
+
+    fn firmado(subject: &[(Nid, &str)], issuer: Option<(&X509, &PKey<Private>)>) -> (X509, PKey<Private>) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        for (nid, val) in subject {
+            name.append_entry_by_nid(*nid, val).unwrap();
+        }
+        let name = name.build();
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(issuer.map_or(&*name, |(ca, _)| ca.subject_name())).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(30).unwrap()).unwrap();
+        if issuer.is_none() {
+            let bc = openssl::x509::extension::BasicConstraints::new().critical().ca().build().unwrap();
+            cert.append_extension(bc).unwrap();
+        }
+        cert.sign(issuer.map_or(&key, |(_, ca_key)| ca_key), MessageDigest::sha256()).unwrap();
+        (cert.build(), key)
+    }
+
+    #[test]
+    fn verifica_cadena_y_vigencia() {
+        let (ca, ca_key) = firmado(&[(Nid::COMMONNAME, "Computadores Test")], None);
+        let (falsa, falsa_key) = firmado(&[(Nid::COMMONNAME, "Computadores Test")], None);
+        let subject = [(Nid::COMMONNAME, "facturador"), (Nid::SERIALNUMBER, "CUIT 20111111112")];
+        let (cert, key) = firmado(&subject, Some((&ca, &ca_key)));
+        let (forjado, forjado_key) = firmado(&subject, Some((&falsa, &falsa_key)));
+
+        let trust = ArcaTrust::from_pem(b"", &ca.to_pem().unwrap()).unwrap();
+        let pem = |k: &PKey<Private>| k.private_key_to_pem_pkcs8().unwrap();
+
+        let ok = inspect_cert(cert.to_pem().unwrap(), &ArcaEnvironment::Homologation, CUIT, pem(&key), None, &trust, Utc::now());
+        assert!(ok.errors.is_empty(), "{:?}", ok.errors);
+        assert_eq!(ok.subject_cn.as_deref(), Some("facturador"));
+        assert_eq!((ok.key_algorithm.as_deref(), ok.key_bits), (Some("RSA"), Some(2048)));
+        assert_eq!(ok.sha256_fingerprint.unwrap().len(), 32 * 3 - 1);
+        assert!(matches!(ok.warnings[..], [CertWarning::ExpiresSoon { days: 29 }]), "{:?}", ok.warnings);
+
+        let forjado = inspect_cert(forjado.to_pem().unwrap(), &ArcaEnvironment::Homologation, CUIT, pem(&forjado_key), None, &trust, Utc::now());
+        assert!(matches!(forjado.errors[..], [CertError::UntrustedChain]), "{:?}", forjado.errors);
+        assert_eq!(forjado.errors[0].code(), "CERT_UNTRUSTED_CHAIN");
+
+        let vencido = inspect_cert(cert.to_pem().unwrap(), &ArcaEnvironment::Homologation, CUIT, pem(&key), None, &trust, Utc::now() + Duration::days(60));
+        assert!(vencido.errors.iter().any(|e| matches!(e, CertError::Expired)), "{:?}", vencido.errors);
+
+        let sin_ca = inspect_cert(cert.to_pem().unwrap(), &ArcaEnvironment::Production, CUIT, pem(&key), None, &trust, Utc::now());
+        assert!(sin_ca.errors.iter().any(|e| matches!(e, CertError::MissingTrustAnchors)));
+    }
+    #[test]
+    fn bundled_tiene_certificados() {
+        let trust = ArcaTrust::bundled().unwrap_or_else(|err| panic!("{err}"));
+        assert!(!trust.prod.is_empty());
+        assert!(!trust.homo.is_empty());
+    }
+}
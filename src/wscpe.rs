@@ -1,7 +1,7 @@
-#[cfg(feature = "wsmtxca_url")]
+#[cfg(feature = "wscpe_url")]
 mod url;
 
-#[cfg(feature = "wsmtxca_dummy")]
-mod service_status;
-#[cfg(feature = "wsmtxca_dummy")]
-pub use service_status::service_status;
\ No newline at end of file
+#[cfg(feature = "wscpe_dummy")]
+pub(crate) mod service_status;
+#[cfg(feature = "wscpe_dummy")]
+pub use service_status::service_status;
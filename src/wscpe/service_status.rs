@@ -1,69 +1,24 @@
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-use reqwest::{header::CONTENT_TYPE, Client};
+use crate::{transport::SoapTransport, types::{dummy_result::{consultar, DummyRequest}, enums::{ArcaEnvironment, Webservice}, FEDummyResult}, wscpe::url::{WSCPE_URL_HOMO, WSCPE_URL_PROD}};
 
-use crate::{types::FEDummyResult, wscpe::url::{WSCPE_URL_HOMO, WSCPE_URL_PROD}, xml_utils::get_xml_tag};
-
-/// Consulta el metodo FEDummy para saber si el servicio esta corriendo o no
-pub async fn service_status(req_cli : &Client, es_prod:bool, timeout:Option<Duration>) -> FEDummyResult {
-	let mut retorno = FEDummyResult {
-		status     : reqwest::StatusCode::BAD_REQUEST,
-		app_server : false,
-		db_server  : false,
-		auth_server: false,
-		milis_respuesta : 0,
-	};
+/// Consulta el metodo FEDummy para saber si el servicio esta corriendo o no.
+/// `transport` puede ser un `reqwest::Client` o cualquier otro `SoapTransport`
+pub async fn service_status<T: SoapTransport>(transport : &T, environment:&ArcaEnvironment, timeout:Option<Duration>) -> FEDummyResult {
+	return consultar(transport, &dummy_request(environment), timeout).await;
+}
 
-	let url = if es_prod {WSCPE_URL_PROD} else {WSCPE_URL_HOMO};
-	
-	let send_xml = 
+pub(crate) fn dummy_request(environment:&ArcaEnvironment) -> DummyRequest<'_> {
+	return DummyRequest {
+		webservice	: Webservice::Wscpe,
+		url					: environment.resolve(&Webservice::Wscpe, WSCPE_URL_PROD, WSCPE_URL_HOMO),
+		body				: 
 r#"<soapenv:Envelope xmlns:soapenv="http://schemas.xmlsoap.org/soap/envelope/">
  <soapenv:Body>
   <tns:dummy xmlns:tns="https://serviciosjava.afip.gob.ar/wscpe/"></tns:dummy>
  </soapenv:Body>
-</soapenv:Envelope>"#;
-
-	let start = Instant::now();
-	let req = req_cli.post(url)
-	.header(CONTENT_TYPE, "text/xml")
-	.body(send_xml)
-	.timeout(timeout.unwrap_or(Duration::from_secs(30)))
-	.send().await;
-
-	retorno.milis_respuesta = start.elapsed().as_millis();
-	
-
-	match req {
-		Ok(online) => {
-			retorno.status = online.status();
-
-			let txt = online.text().await.unwrap();
-			retorno.app_server  = get_xml_tag(&txt, "appserver" ).map(|x| x.to_uppercase().trim() == "OK").unwrap_or(false);
-			retorno.db_server   = get_xml_tag(&txt, "dbserver"  ).map(|x| x.to_uppercase().trim() == "OK").unwrap_or(false);
-			retorno.auth_server = get_xml_tag(&txt, "authserver").map(|x| x.to_uppercase().trim() == "OK").unwrap_or(false);
-		},
-		Err(er) => {
-			match er.status() {
-				Some(status) => {retorno.status = status},
-				None => {
-					if er.is_connect() {
-						dbg!(&er);
-						retorno.status = reqwest::StatusCode::SERVICE_UNAVAILABLE;
-					} else if er.is_request() {
-						dbg!(&er);
-						retorno.status = reqwest::StatusCode::BAD_REQUEST;
-					} else if er.is_timeout() {
-						dbg!(&er);
-						retorno.status = reqwest::StatusCode::REQUEST_TIMEOUT;
-					} else {
-						dbg!(&er);
-						retorno.status = reqwest::StatusCode::INTERNAL_SERVER_ERROR;
-					}
-				},
-			}
-		},
-	}
-
-	return retorno;
-}
-
+</soapenv:Envelope>"#,
+		soap_action	: None,
+		tags				: ["appserver", "dbserver", "authserver"],
+	};
+}
\ No newline at end of file
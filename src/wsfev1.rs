@@ -5,6 +5,6 @@ mod url;
 pub mod fe_cae_solicitar;
 
 #[cfg(feature = "wsfev1_dummy")]
-mod service_status;
+pub(crate) mod service_status;
 #[cfg(feature = "wsfev1_dummy")]
 pub use service_status::service_status;
\ No newline at end of file
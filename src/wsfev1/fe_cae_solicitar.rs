@@ -1,3 +1,5 @@
 pub mod generar_request;
 pub mod parse_response;
-pub mod types;
\ No newline at end of file
+pub mod types;
+#[cfg(feature = "blocking")]
+pub(crate) mod blocking;
@@ -0,0 +1,30 @@
+use crate::{transport::{SoapRequest, SoapTransport}, types::{enums::{ArcaEnvironment, Webservice}, errors::ErrType}, wsaa::{blocking::get_token, config::WsaaConfig, get_token::{CertKeyPair, ServiceId}, token_store::TokenStore}};
+
+use super::generar_request::{armar_request, token_parser, Comprobante};
+
+/// Igual que `generar_request`, pero sincronica. Ver `crate::blocking`.
+/// El request se envia con `crate::blocking::send`
+#[allow(clippy::too_many_arguments)]
+pub fn generar_request<S, T, Fc>(
+	token_store			: &S,
+	tenant_id				: i64,
+	environment			: &ArcaEnvironment,
+	cuit_representado: Option<i64>,
+	transport				: &T,
+	config					: &WsaaConfig,
+	comprobante		 	: &Comprobante,
+	cert_key_getter	: Fc,
+) -> Result<SoapRequest, ErrType>
+where 
+	S: TokenStore,
+	T: SoapTransport,
+	Fc: FnMut(ServiceId) -> Option<CertKeyPair>,
+{
+	let key = ServiceId::new(tenant_id, Webservice::Wsfev1, environment.clone());
+	let mut cuit = cuit_representado;
+	let auth_xml = get_token(token_store, key, transport, config, cert_key_getter, |_, cuit_cert, token, sign| {
+		token_parser(*cuit.get_or_insert(cuit_cert), token, sign)
+	})?;
+
+	return Ok(armar_request(environment, comprobante, auth_xml, cuit));
+}
@@ -1,39 +1,56 @@
-use std::{sync::Arc, time::Duration};
+use std::time::Duration;
 
 use chrono::NaiveDate;
-use reqwest::{header::CONTENT_TYPE, Client, RequestBuilder};
 
-use crate::{types::{enums::Webservice, errors::ErrType}, wsaa::get_token::{get_token, CertKeyPair, ServiceId, TokenArca}, wsfev1::url::{WSFEV1_URL_HOMO, WSFEV1_URL_PROD}};
+use crate::{transport::{SoapRequest, SoapTransport}, types::{enums::{ArcaEnvironment, Webservice}, errors::ErrType}, wsaa::{config::WsaaConfig, get_token::{get_token, CertKeyPair, ServiceId}, token_store::TokenStore}, wsfev1::url::{WSFEV1_URL_HOMO, WSFEV1_URL_PROD}};
 
 /// Genera el request completamente, incluyendo auth y contenido, pero no lo envia.
-/// De esta forma podes logear el contenido antes de enviarlo
-/// `cert_key_getter` Solo se llama si es necesario renovar el token
-pub async fn generar_request<Fc>(
-	token_map				: Arc<dashmap::DashMap<ServiceId, TokenArca>>,
+/// De esta forma podes logear el contenido (`body`) antes de enviarlo con `transport.send(req)`.
+/// `transport` solo se usa para renovar el token en WSAA
+/// `cert_key_getter` Solo se llama si es necesario renovar el token. Recibe el `ServiceId` del token, que indica el ambiente.
+/// `environment` define las URLs de WSAA y WSFEv1 y separa los tokens de cada ambiente
+///
+/// `cuit_representado` es el CUIT en nombre del cual se factura. Si es `None` se usa el del certificado.
+/// El token se sigue guardando por `tenant_id`, asi que un mismo certificado autentica una sola vez para todos sus representados.
+/// Se puede verificar la delegacion antes de facturar con `TokenArca::sso`
+#[allow(clippy::too_many_arguments)]
+pub async fn generar_request<S, T, Fc>(
+	token_store			: &S,
 	tenant_id				: i64,
-	es_prod					: bool,
-	req_cli					: &Client,
+	environment			: &ArcaEnvironment,
+	cuit_representado: Option<i64>,
+	transport				: &T,
+	config					: &WsaaConfig,
 	comprobante		 	: &Comprobante,
 	cert_key_getter	: Fc,
-) -> Result<(RequestBuilder, String), ErrType>
+) -> Result<SoapRequest, ErrType>
 where 
-	Fc: AsyncFnMut() -> Option<CertKeyPair>,
+	S: TokenStore,
+	T: SoapTransport,
+	Fc: AsyncFnMut(ServiceId) -> Option<CertKeyPair>,
 {
-	let url = if es_prod {WSFEV1_URL_PROD} else {WSFEV1_URL_HOMO};
-	let key = ServiceId{ tenant_id, webservice: Webservice::Wsfev1 };
-	let auth_xml = get_token(token_map, key, es_prod, req_cli, cert_key_getter, token_parser).await?;
+	let key = ServiceId::new(tenant_id, Webservice::Wsfev1, environment.clone());
+	let mut cuit = cuit_representado;
+	let auth_xml = get_token(token_store, key, transport, config, cert_key_getter, |_, cuit_cert, token, sign| {
+		token_parser(*cuit.get_or_insert(cuit_cert), token, sign)
+	}).await?;
 
-	let send_xml = xml_make(comprobante, auth_xml);
-
-	let req = req_cli.post(url)
-	.header(CONTENT_TYPE, "application/soap+xml")
-	.body(send_xml.clone())
-	.timeout(Duration::from_secs(60));
+	return Ok(armar_request(environment, comprobante, auth_xml, cuit));
+}
 
-	return Ok((req, send_xml));
+pub(super) fn armar_request(environment:&ArcaEnvironment, comprobante:&Comprobante, auth_xml:String, cuit:Option<i64>) -> SoapRequest {
+	return SoapRequest {
+		url						: environment.resolve(&Webservice::Wsfev1, WSFEV1_URL_PROD, WSFEV1_URL_HOMO).to_owned(),
+		soap_action		: None,
+		content_type	: "application/soap+xml".to_owned(),
+		body					: xml_make(comprobante, auth_xml),
+		timeout				: Some(Duration::from_secs(60)),
+		cuit,
+		webservice		: Some(Webservice::Wsfev1),
+	};
 }
 
-fn token_parser(cuit:i64, token:&str, sign:&str ) -> String {
+pub(super) fn token_parser(cuit:i64, token:&str, sign:&str ) -> String {
 	format!(
 r#"<ar:Auth>
 	<ar:Token>{token}</ar:Token>
@@ -138,7 +155,7 @@ fn xml_make(comp: &Comprobante, auth_xml:String) -> String {
 	let cancela_misma_moneda = if *cancela_misma_moneda {'S'} else {'N'};
 
 	if COMP_TIPO_C.contains(tipo_rg1415) {
-		val_gravado = val_nogravado.clone();
+		val_gravado = val_nogravado;
 		val_nogravado = 0.0;
 	}
 
@@ -177,7 +194,7 @@ r#"<ar:PeriodoAsoc>
 	} else {String::new()};
 
 	let comp_asoc = cbte_asoc_xml(&comp.comprob_asociados);
-	let tribut = tributos_xml(&tributos);
+	let tribut = tributos_xml(tributos);
 	let iva = ivaalic_xml(alicuotas_iva);
 	let opcion = opcion_xml(&comp.opcionales);
 	let activid = actividades_xml(&comp.actividades);
@@ -229,13 +246,12 @@ format!(r#"<soap:Envelope xmlns:soap="http://www.w3.org/2003/05/soap-envelope" x
 
 
 fn cbte_asoc_xml(com:&Option<Vec<ComprobAsoc>>) -> String {
-	if let Some(asoc) = com {
-		if asoc.len() > 0 {
-			let ar = asoc.iter()
-			.map(|f|{
-				let ComprobAsoc { punto_venta, num_documento, tipo_rg1415, fecha_emision} = &f;
-				let fecha_emision = fecha_emision.format("%Y%m%d").to_string();
-				format!(
+	if let Some(asoc) = com && !asoc.is_empty() {
+		let ar = asoc.iter()
+		.map(|f|{
+			let ComprobAsoc { punto_venta, num_documento, tipo_rg1415, fecha_emision} = &f;
+			let fecha_emision = fecha_emision.format("%Y%m%d").to_string();
+			format!(
 r#"
 <ar:CbteAsoc>
 	<ar:Tipo>{tipo_rg1415}</ar:Tipo>
@@ -243,13 +259,12 @@ r#"
 	<ar:Nro>{num_documento}</ar:Nro>
 	<ar:CbteFch>{fecha_emision}</ar:CbteFch>
 </ar:CbteAsoc>"#)
-			})
-			.reduce(|acc, val| {
-				acc + &val
-			}).unwrap();
+		})
+		.reduce(|acc, val| {
+			acc + &val
+		}).unwrap();
 
-			return format!(r#"<ar:CbtesAsoc>{ar}</ar:CbtesAsoc>"#)
-		}	
+		return format!(r#"<ar:CbtesAsoc>{ar}</ar:CbtesAsoc>"#)
 	};
 
 	String::new()
@@ -257,12 +272,11 @@ r#"
 
 
 fn tributos_xml(trib:&Option<Vec<ComprobTributos>>) -> String {
-	if let Some(trib) = trib {
-		if trib.len() > 0 {
-			let ar = trib.iter()
-			.map(|f|{
-				let ComprobTributos { id_tributo, desc, base, alicuota, importe } = f;
-				format!(
+	if let Some(trib) = trib && !trib.is_empty() {
+		let ar = trib.iter()
+		.map(|f|{
+			let ComprobTributos { id_tributo, desc, base, alicuota, importe } = f;
+			format!(
 r#"<ar:Tributo>
 	<ar:Id>{id_tributo}</ar:Id>
 	<ar:Desc>{desc}</ar:Desc>
@@ -270,13 +284,12 @@ r#"<ar:Tributo>
 	<ar:Alic>{alicuota}</ar:Alic>
 	<ar:Importe>{importe}</ar:Importe>
 </ar:Tributo>"#)
-			})
-			.reduce(|acc, val| {
-				acc + &val
-			}).unwrap();
+		})
+		.reduce(|acc, val| {
+			acc + &val
+		}).unwrap();
 
-			return format!(r#"<ar:Tributos>{ar}</ar:Tributos>"#)
-		}	
+		return format!(r#"<ar:Tributos>{ar}</ar:Tributos>"#)
 	};
 
 	String::new()
@@ -284,24 +297,22 @@ r#"<ar:Tributo>
 
 
 fn ivaalic_xml(iva:&Option<Vec<ComprobIVA>>) -> String {
-	if let Some(iva) = iva {
-		if iva.len() > 0 {
-			let ar = iva.iter()
-			.map(|f|{
-				let ComprobIVA { id_alicuota, base, importe } = f;
-				format!(
+	if let Some(iva) = iva && !iva.is_empty() {
+		let ar = iva.iter()
+		.map(|f|{
+			let ComprobIVA { id_alicuota, base, importe } = f;
+			format!(
 r#"<ar:AlicIva>
 	<ar:Id>{id_alicuota}</ar:Id>
 	<ar:BaseImp>{base}</ar:BaseImp>
 	<ar:Importe>{importe}</ar:Importe>
 </ar:AlicIva>"#)
-			})
-			.reduce(|acc, val| {
-				acc + &val
-			}).unwrap();
+		})
+		.reduce(|acc, val| {
+			acc + &val
+		}).unwrap();
 
-			return format!(r#"<ar:Iva>{ar}</ar:Iva>"#)
-		}	
+		return format!(r#"<ar:Iva>{ar}</ar:Iva>"#)
 	};
 
 	String::new()
@@ -309,23 +320,21 @@ r#"<ar:AlicIva>
 
 
 fn opcion_xml(iva:&Option<Vec<ComprobOpcionales>>) -> String {
-	if let Some(iva) = iva {
-		if iva.len() > 0 {
-			let ar = iva.iter()
-			.map(|f|{
-				let ComprobOpcionales { id, valor } = f;
-				format!(
+	if let Some(iva) = iva && !iva.is_empty() {
+		let ar = iva.iter()
+		.map(|f|{
+			let ComprobOpcionales { id, valor } = f;
+			format!(
 r#"<ar:Opcional>
 	<ar:Id>{id}</ar:Id>
 	<ar:Valor>{valor}</ar:Valor>
 </ar:Opcional>"#)
-			})
-			.reduce(|acc, val| {
-				acc + &val
-			}).unwrap();
+		})
+		.reduce(|acc, val| {
+			acc + &val
+		}).unwrap();
 
-			return format!(r#"<ar:Opcionales>{ar}</ar:Opcionales>"#)
-		}	
+		return format!(r#"<ar:Opcionales>{ar}</ar:Opcionales>"#)
 	};
 
 	String::new()
@@ -333,19 +342,17 @@ r#"<ar:Opcional>
 
 
 fn actividades_xml(activ:&Option<Vec<String>>) -> String {
-	if let Some(activ) = activ {
-		if activ.len() > 0 {
-			let ar = activ.iter()
-			.map(|f|{
-				format!(
+	if let Some(activ) = activ && !activ.is_empty() {
+		let ar = activ.iter()
+		.map(|f|{
+			format!(
 r#"<ar:Actividad>
 	<ar:Id>{f}</ar:Id>
 </ar:Actividad>"#)})
-			.reduce(|acc, val| {acc + &val})
-			.unwrap();
+		.reduce(|acc, val| {acc + &val})
+		.unwrap();
 
-			return format!(r#"<ar:Actividades>{ar}</ar:Actividades>"#)
-		}	
+		return format!(r#"<ar:Actividades>{ar}</ar:Actividades>"#)
 	};
 
 	String::new()
@@ -1,11 +1,10 @@
 use chrono::{Days, NaiveDate, Utc};
-use reqwest::StatusCode;
 
 use crate::{types::errors::{ErrType, SoapFault}, wsfev1::fe_cae_solicitar::types::{Wsfev1Obs, Wsfev1Ok}, xml_utils::{get_xml_tag, get_xml_vec}};
 
 pub fn parse_response(
 	respuesta : &str,
-	status    : StatusCode,
+	status    : u16,
 ) -> Result<Wsfev1Ok, ErrType> {
 
 	if respuesta.contains("<soap:Fault>"){
@@ -62,7 +61,7 @@ pub fn parse_response(
 					}
 				}
 			} else {
-				if obs.len() > 0 {
+				if !obs.is_empty() {
 
 					if let Some(er) =  obs.iter().find(|x| x.code=="10016") {
 						return Err(SoapFault::new(&er.code, &er.msg).into());
@@ -76,4 +75,20 @@ pub fn parse_response(
 			}
 		},
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::transport::Fixture;
+
+	use super::*;
+
+	#[test]
+	fn parsea_fixture() {
+		let fixture: Fixture = serde_json::from_str(include_str!("../../../fixtures/wsfev1_fe_cae_solicitar/000_FECAESolicitar.json")).unwrap();
+		let ok = parse_response(&fixture.response, fixture.status).unwrap();
+		assert_eq!(ok.cae, "76421234567890");
+		assert_eq!(ok.vcto, NaiveDate::from_ymd_opt(2026, 10, 28).unwrap());
+		assert_eq!(ok.obs[0].code, "10217");
+	}
+}
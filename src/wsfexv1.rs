@@ -2,6 +2,6 @@
 mod url;
 
 #[cfg(feature = "wsfexv1_dummy")]
-mod service_status;
+pub(crate) mod service_status;
 #[cfg(feature = "wsfexv1_dummy")]
 pub use service_status::service_status;
\ No newline at end of file
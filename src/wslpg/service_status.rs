@@ -1,80 +1,38 @@
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-use reqwest::{header::CONTENT_TYPE, Client};
+use crate::{transport::SoapTransport, types::{dummy_result::{consultar, DummyRequest}, enums::{ArcaEnvironment, Webservice}, FEDummyResult}, wslpg::url::{WSLPG_URL_HOMO, WSLPG_URL_PROD}};
 
-use crate::{types::FEDummyResult, wslpg::url::{WSLPG_URL_HOMO, WSLPG_URL_PROD}, xml_utils::get_xml_tag};
-
-/// Consulta el metodo FEDummy para saber si el servicio esta corriendo o no
-pub async fn service_status(req_cli : &Client, es_prod:bool, timeout:Option<Duration>) -> FEDummyResult {
-	let mut retorno = FEDummyResult {
-		status     : reqwest::StatusCode::BAD_REQUEST,
-		app_server : false,
-		db_server  : false,
-		auth_server: false,
-		milis_respuesta : 0,
-	};
+/// Consulta el metodo FEDummy para saber si el servicio esta corriendo o no.
+/// `transport` puede ser un `reqwest::Client` o cualquier otro `SoapTransport`
+pub async fn service_status<T: SoapTransport>(transport : &T, environment:&ArcaEnvironment, timeout:Option<Duration>) -> FEDummyResult {
+	return consultar(transport, &dummy_request(environment), timeout).await;
+}
 
-	let url = if es_prod {WSLPG_URL_PROD} else {WSLPG_URL_HOMO};
-	
-	let send_xml = 
+pub(crate) fn dummy_request(environment:&ArcaEnvironment) -> DummyRequest<'_> {
+	return DummyRequest {
+		webservice	: Webservice::Wslpg,
+		url					: environment.resolve(&Webservice::Wslpg, WSLPG_URL_PROD, WSLPG_URL_HOMO),
+		body				: 
 r#"<soapenv:Envelope xmlns:soapenv="http://schemas.xmlsoap.org/soap/envelope/">
  <soapenv:Header/>
  <soapenv:Body/>
-</soapenv:Envelope>"#;
-
-	let start = Instant::now();
-	let req = req_cli.post(url)
-	.header(CONTENT_TYPE, "text/xml")
-	.header("SOAPAction", "http://serviciosjava.afip.gob.ar/wslpg/dummy")
-	.body(send_xml)
-	.timeout(timeout.unwrap_or(Duration::from_secs(30)))
-	.send().await;
-
-	retorno.milis_respuesta = start.elapsed().as_millis();
-	
-	match req {
-		Ok(online) => {
-			retorno.status = online.status();
-
-			let txt = online.text().await.unwrap();
-			retorno.app_server  = get_xml_tag(&txt, "appserver" ).map(|x| x.to_uppercase().trim() == "OK").unwrap_or(false);
-			retorno.db_server   = get_xml_tag(&txt, "dbserver"  ).map(|x| x.to_uppercase().trim() == "OK").unwrap_or(false);
-			retorno.auth_server = get_xml_tag(&txt, "authserver").map(|x| x.to_uppercase().trim() == "OK").unwrap_or(false);
-		},
-		Err(er) => {
-			match er.status() {
-				Some(status) => {
-					retorno.status = status
-					
-				},
-				None => {
-					if er.is_connect() {
-						dbg!(&er);
-						retorno.status = reqwest::StatusCode::SERVICE_UNAVAILABLE;
-					} else if er.is_request() {
-						dbg!(&er);
-						retorno.status = reqwest::StatusCode::BAD_REQUEST;
-					} else if er.is_timeout() {
-						dbg!(&er);
-						retorno.status = reqwest::StatusCode::REQUEST_TIMEOUT;
-					} else {
-						dbg!(&er);
-						retorno.status = reqwest::StatusCode::INTERNAL_SERVER_ERROR;
-					}
-				},
-			}
-		},
-	}
-
-	return retorno;
+</soapenv:Envelope>"#,
+		soap_action	: Some("http://serviciosjava.afip.gob.ar/wslpg/dummy"),
+		tags				: ["appserver", "dbserver", "authserver"],
+	};
 }
 
-
 #[cfg(test)]
 mod tests {
+	use crate::transport::ReplayTransport;
+
+	use super::*;
+
 	#[tokio::test]
-	async fn status() {
-		let cli = reqwest::Client::new();
-		super::service_status(&cli, true, None).await;
+	async fn dummy_con_fixture() {
+		let transport = ReplayTransport::from_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/wslpg_dummy")).unwrap();
+		let res = service_status(&transport, &ArcaEnvironment::Homologation, None).await;
+		assert_eq!(res.status, 200);
+		assert!(res.app_server && res.db_server && res.auth_server);
 	}
-}
\ No newline at end of file
+}
@@ -1,4 +1,10 @@
 mod get_xml_tag;
+#[cfg(feature = "wsaa")]
+mod get_xml_attr;
 
 pub use get_xml_tag::get_xml_tag;
 pub use get_xml_tag::get_xml_vec;
+#[cfg(feature = "wsaa")]
+pub use get_xml_attr::get_xml_attr;
+#[cfg(feature = "wsaa")]
+pub use get_xml_attr::{get_xml_open_tags, get_tag_attr};
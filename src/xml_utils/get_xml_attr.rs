@@ -0,0 +1,30 @@
+///Devuelve el atributo `attr` de la primera aparicion de `tag`
+pub fn get_xml_attr(xml: &str, tag:&str, attr:&str) -> Option<String> {
+	return get_xml_attr_vec(xml, tag, attr).into_iter().next();
+}
+
+///Devuelve el atributo `attr` de cada aparicion de `tag`, salteando las que no lo tienen
+fn get_xml_attr_vec(xml: &str, tag:&str, attr:&str) -> Vec<String> {
+	return get_xml_open_tags(xml, tag).into_iter()
+		.filter_map(|open_tag| get_tag_attr(open_tag, attr))
+		.collect();
+}
+
+///Devuelve cada aparicion de `tag` hasta el `>` que la abre, para leer juntos los atributos de un mismo elemento
+pub fn get_xml_open_tags<'a>(xml: &'a str, tag:&str) -> Vec<&'a str> {
+	let start_tag = format!("<{tag}");
+
+	return xml.split(&start_tag)
+		.skip(1)
+		//Descarta tags que solo empiezan igual, por ejemplo `<relations` cuando se busca `<relation`
+		.filter(|rest| rest.starts_with(|c:char| c.is_whitespace() || c == '>' || c == '/'))
+		.filter_map(|rest| rest.split('>').next())
+		.collect();
+}
+
+///Devuelve el atributo `attr` de un tag de `get_xml_open_tags`
+pub fn get_tag_attr(open_tag: &str, attr:&str) -> Option<String> {
+	let attr_start = format!(" {attr}=\"");
+	let value = open_tag.split(&attr_start).nth(1)?;
+	return value.split('"').next().map(str::to_owned);
+}
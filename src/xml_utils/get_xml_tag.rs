@@ -1,7 +1,7 @@
 ///Obtiene un unico tag del XML
 pub fn get_xml_tag(xml: &str, tag:&str) -> Option<String> {
 	let result = get_xml_vec(xml, tag);
-	if result.len() == 0 {
+	if result.is_empty() {
 		return None
 	} else {
 		return Some(result[0].clone());
@@ -20,7 +20,7 @@ pub fn get_xml_vec(xml: &str, tag:&str) -> Vec<String> {
 	let slice = String::from(xml)
 	.split(&start_tag)
 	.skip(1)
-	.map(|x| x.split(&end_tag).nth(0).unwrap().to_owned())
+	.map(|x| x.split(&end_tag).next().unwrap().to_owned())
 	.collect();
 
 	//dbg!(&xml, &start_tag, &end_tag, &slice);